@@ -0,0 +1,47 @@
+//! Renders reports over a 10k line input to keep an eye on how the line lookup
+//! scales with the size of the code and the number of messages.
+//!
+//! Run with `cargo bench --bench large_input`.
+
+use std::time::Instant;
+
+use lyneate::Report;
+
+const LINES: usize = 10_000;
+const RUNS: u32 = 20;
+
+fn main() {
+    let code = (0..LINES)
+        .map(|i| {
+            format!(
+                "let value_{i} = compute({i}, value_{});\n",
+                i.saturating_sub(1)
+            )
+        })
+        .collect::<String>();
+
+    let line_starts = std::iter::once(0)
+        .chain(code.match_indices('\n').map(|(i, _)| i + 1))
+        .collect::<Vec<_>>();
+
+    for step in [1000, 100, 10] {
+        let messages = (0..LINES)
+            .step_by(step)
+            .map(|l| {
+                let start = line_starts[l] + 4;
+                (start..start + 5, "this value", (255, 64, 112))
+            })
+            .collect::<Vec<_>>();
+
+        let start = Instant::now();
+        for _ in 0..RUNS {
+            let report = Report::new_byte_spanned(&code, messages.clone());
+            std::hint::black_box(report.display_str());
+        }
+        println!(
+            "{:>5} messages over {LINES} lines: {:?} per report",
+            messages.len(),
+            start.elapsed() / RUNS
+        );
+    }
+}
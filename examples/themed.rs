@@ -1,5 +1,5 @@
 use colored::Colorize;
-use lyneate::{Report, Theme, ThemeChars, ThemeSizing};
+use lyneate::prelude::*;
 
 fn main() {
     println!(
@@ -0,0 +1,251 @@
+use annotate_snippets::snippet::{AnnotationType, Slice, Snippet};
+
+use crate::{report::Color, MultiReport, Report, Severity};
+
+// notes and help have no severity of their own, so they get their own colors to
+// tell them apart from info, which is shown like a note
+fn annotation_color(annotation_type: AnnotationType) -> Color {
+    match annotation_type {
+        AnnotationType::Error => Severity::Error.color(),
+        AnnotationType::Warning => Severity::Warning.color(),
+        AnnotationType::Info => Severity::Note.color(),
+        AnnotationType::Note => (176, 128, 255),
+        AnnotationType::Help => (64, 224, 96),
+    }
+}
+
+impl<'a> Report<'a> {
+    /// Creates a report from an [`annotate_snippets`] slice, with a message for
+    /// each of its annotations colored by the annotation type. The origin of the
+    /// slice becomes the source name.
+    ///
+    /// Like in `annotate_snippets`, the annotation ranges are char offsets into
+    /// the source, and its lines are numbered from `line_start`. The `fold` of
+    /// the slice is not used.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// use annotate_snippets::snippet::{AnnotationType, Slice, SourceAnnotation};
+    ///
+    /// let slice = Slice {
+    ///     source: "let x: u32 = \"five\";",
+    ///     line_start: 1,
+    ///     origin: Some("main.rs"),
+    ///     annotations: vec![
+    ///         SourceAnnotation {
+    ///             range: (13, 19),
+    ///             label: "expected `u32`, found `&str`",
+    ///             annotation_type: AnnotationType::Error,
+    ///         },
+    ///         SourceAnnotation {
+    ///             range: (7, 10),
+    ///             label: "expected due to this",
+    ///             annotation_type: AnnotationType::Info,
+    ///         },
+    ///     ],
+    ///     fold: false,
+    /// };
+    /// let plain = Report::from_annotate_snippets(&slice).render_plain();
+    ///
+    /// assert!(plain.contains("main.rs:1:8"));
+    /// assert!(plain.contains("expected `u32`, found `&str`"));
+    /// assert!(plain.contains("expected due to this"));
+    /// ```
+    pub fn from_annotate_snippets(slice: &Slice<'a>) -> Self {
+        let report = Report::new_char_spanned(
+            slice.source,
+            slice.annotations.iter().map(|annotation| {
+                (
+                    annotation.range.0..annotation.range.1,
+                    annotation.label,
+                    annotation_color(annotation.annotation_type),
+                )
+            }),
+        );
+        let report = report.with_first_line(slice.line_start);
+        match slice.origin {
+            Some(origin) => report.with_source_name(origin),
+            None => report,
+        }
+    }
+}
+
+impl<'a> MultiReport<'a> {
+    /// Creates a report from an [`annotate_snippets`] snippet, with a section for
+    /// each of its slices (see [`Report::from_annotate_snippets`]).
+    ///
+    /// The title and footer of the snippet are not shown.
+    ///
+    /// ```
+    /// # use lyneate::MultiReport;
+    /// use annotate_snippets::snippet::{AnnotationType, Slice, Snippet, SourceAnnotation};
+    ///
+    /// let slice = |origin, source, range, label| Slice {
+    ///     source,
+    ///     line_start: 1,
+    ///     origin: Some(origin),
+    ///     annotations: vec![SourceAnnotation {
+    ///         range,
+    ///         label,
+    ///         annotation_type: AnnotationType::Note,
+    ///     }],
+    ///     fold: false,
+    /// };
+    /// let snippet = Snippet {
+    ///     slices: vec![
+    ///         slice("lib.rs", "pub fn f() {}", (7, 8), "defined here"),
+    ///         slice("main.rs", "fn main() { f(1); }", (12, 16), "used here"),
+    ///     ],
+    ///     ..Default::default()
+    /// };
+    /// let plain = MultiReport::from_annotate_snippets(&snippet).display_str();
+    ///
+    /// assert!(plain.contains("lib.rs:1:8"));
+    /// assert!(plain.contains("main.rs:1:13"));
+    /// ```
+    pub fn from_annotate_snippets(snippet: &Snippet<'a>) -> Self {
+        snippet
+            .slices
+            .iter()
+            .map(Report::from_annotate_snippets)
+            .fold(MultiReport::new(), MultiReport::with_section)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use annotate_snippets::snippet::{AnnotationType, Slice, Snippet, SourceAnnotation};
+
+    use super::annotation_color;
+    use crate::{MultiReport, Report, Severity};
+
+    fn slice<'a>(
+        origin: &'a str,
+        source: &'a str,
+        line_start: usize,
+        annotations: Vec<SourceAnnotation<'a>>,
+    ) -> Slice<'a> {
+        Slice {
+            source,
+            line_start,
+            origin: Some(origin),
+            annotations,
+            fold: false,
+        }
+    }
+
+    fn annotation(
+        range: (usize, usize),
+        label: &str,
+        annotation_type: AnnotationType,
+    ) -> SourceAnnotation<'_> {
+        SourceAnnotation {
+            range,
+            label,
+            annotation_type,
+        }
+    }
+
+    fn plain_rows(report: &Report) -> Vec<String> {
+        report.render_plain().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn slice_from_further_into_a_file_keeps_its_line_numbers() {
+        let report = Report::from_annotate_snippets(&slice(
+            "main.rs",
+            "fn main() {\n    let x: u32 = \"five\";\n}",
+            41,
+            vec![annotation(
+                (29, 35),
+                "expected `u32`",
+                AnnotationType::Error,
+            )],
+        ));
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   --> main.rs:42:18",
+                "   42.      let x: u32 = \"five\"; ",
+                "                         ------ ",
+                "                            | ",
+                "                            \\-- expected `u32`",
+            ]
+        );
+    }
+
+    #[test]
+    fn snippet_gets_a_section_for_each_slice() {
+        let snippet = Snippet {
+            slices: vec![
+                slice(
+                    "lib.rs",
+                    "pub fn f() {}",
+                    3,
+                    vec![annotation((7, 8), "defined here", AnnotationType::Note)],
+                ),
+                slice(
+                    "main.rs",
+                    "fn main() { f(1); }",
+                    1,
+                    vec![annotation((12, 16), "used here", AnnotationType::Error)],
+                ),
+            ],
+            ..Default::default()
+        };
+        let display =
+            crate::strip_ansi(&MultiReport::from_annotate_snippets(&snippet).display_str());
+
+        assert_eq!(
+            display.lines().collect::<Vec<_>>(),
+            [
+                "   ──▶ lib.rs:3:8",
+                "   3.  pub fn f() {} ",
+                "              ┬ ",
+                "              │ ",
+                "              ╰── defined here",
+                "",
+                "   ──▶ main.rs:1:13",
+                "   1.  fn main() { f(1); } ",
+                "                   ──┬─ ",
+                "                     │ ",
+                "                     ╰── used here",
+            ]
+        );
+    }
+
+    #[test]
+    fn every_annotation_type_gets_its_color() {
+        let types = [
+            (AnnotationType::Error, Severity::Error.color()),
+            (AnnotationType::Warning, Severity::Warning.color()),
+            (AnnotationType::Info, Severity::Note.color()),
+            (AnnotationType::Note, annotation_color(AnnotationType::Note)),
+            (AnnotationType::Help, annotation_color(AnnotationType::Help)),
+        ];
+        for (annotation_type, color) in types {
+            let report = Report::from_annotate_snippets(&slice(
+                "main.rs",
+                "let value = 5;",
+                1,
+                vec![annotation((4, 9), "here", annotation_type)],
+            ));
+            let colors = report.layout()[1]
+                .cells
+                .iter()
+                .filter_map(|c| c.color)
+                .collect::<Vec<_>>();
+
+            assert_eq!(colors, [color; 5], "{annotation_type:?}");
+        }
+
+        // notes and help are told apart from each other and from info
+        let mut colors = types.map(|(_, color)| color).to_vec();
+        colors.sort_unstable();
+        colors.dedup();
+        assert_eq!(colors.len(), types.len());
+    }
+}
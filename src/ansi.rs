@@ -0,0 +1,72 @@
+//! Terminal styling, done by `colored` with the `std` feature (so that its
+//! color detection and overrides apply) and with plain escape codes without.
+
+#[cfg(not(feature = "std"))]
+use alloc::format;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::string::ToString;
+
+#[cfg(feature = "std")]
+use colored::Colorize;
+
+use crate::{report::Color, wrap::skip_escape};
+
+/// Colors the text of `s` with a 24-bit color.
+pub(crate) fn truecolor(s: &str, (r, g, b): Color) -> String {
+    #[cfg(feature = "std")]
+    return s.truecolor(r, g, b).to_string();
+    #[cfg(not(feature = "std"))]
+    return format!("\x1b[38;2;{r};{g};{b}m{s}\x1b[0m");
+}
+
+/// Colors `s` with a 24-bit color in reverse video, so the color becomes its
+/// background.
+pub(crate) fn truecolor_reversed(s: &str, (r, g, b): Color) -> String {
+    #[cfg(feature = "std")]
+    return s.truecolor(r, g, b).reversed().to_string();
+    #[cfg(not(feature = "std"))]
+    return format!("\x1b[7;38;2;{r};{g};{b}m{s}\x1b[0m");
+}
+
+/// Makes `s` a link to `url` with an OSC 8 escape sequence, which terminals
+/// that don't support it ignore. Left out where colors are turned off, as by
+/// `NO_COLOR` or when not writing to a terminal.
+pub(crate) fn hyperlink(s: &str, url: &str) -> String {
+    #[cfg(feature = "std")]
+    if !colored::control::SHOULD_COLORIZE.should_colorize() {
+        return s.to_string();
+    }
+    format!("\x1b]8;;{url}\x1b\\{s}\x1b]8;;\x1b\\")
+}
+
+/// Dims `s`.
+pub(crate) fn dimmed(s: &str) -> String {
+    #[cfg(feature = "std")]
+    return s.dimmed().to_string();
+    #[cfg(not(feature = "std"))]
+    return format!("\x1b[2m{s}\x1b[0m");
+}
+
+/// Removes the ANSI escape sequences, like colors, from `s`.
+///
+/// This is intended for tests, to compare the layout of colored output
+/// against fixtures without the colors in them.
+///
+/// ```
+/// # use lyneate::strip_ansi;
+/// assert_eq!(strip_ansi("\x1b[38;2;255;64;112mvalue\x1b[0m"), "value");
+/// ```
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            skip_escape(&mut chars);
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
@@ -0,0 +1,66 @@
+use core::fmt;
+
+use crate::report::Color;
+
+/// The error returned by [`color_from_hex`] for text that isn't a hex color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexColorError {
+    /// The color doesn't have 3 or 6 digits, with the number of digits it has.
+    InvalidLength(usize),
+    /// The color contains a char that isn't a hex digit.
+    InvalidChar(char),
+}
+
+impl fmt::Display for HexColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength(len) => {
+                write!(f, "hex color has {len} digits, expected 3 or 6")
+            }
+            Self::InvalidChar(c) => write!(f, "invalid hex digit {c:?} in color"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HexColorError {}
+
+/// Parses a color from hex digits, with an optional leading `#`, in either
+/// the 6-digit `rrggbb` form or the 3-digit `rgb` form, where each digit is
+/// repeated (so `f40` is `ff4400`). Both upper and lower case digits are
+/// accepted.
+///
+/// ```
+/// # use lyneate::{color_from_hex, HexColorError};
+/// assert_eq!(color_from_hex("#ff4070"), Ok((255, 64, 112)));
+/// assert_eq!(color_from_hex("40A0FF"), Ok((64, 160, 255)));
+/// assert_eq!(color_from_hex("#f40"), Ok((255, 68, 0)));
+///
+/// assert_eq!(color_from_hex("#ff40"), Err(HexColorError::InvalidLength(4)));
+/// assert_eq!(color_from_hex("#ff407g"), Err(HexColorError::InvalidChar('g')));
+/// ```
+pub fn color_from_hex(hex: &str) -> Result<Color, HexColorError> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+
+    let mut digits = [0; 6];
+    let mut len = 0;
+    for c in hex.chars() {
+        let digit = c.to_digit(16).ok_or(HexColorError::InvalidChar(c))?;
+        if let Some(d) = digits.get_mut(len) {
+            *d = digit as u8;
+        }
+        len += 1;
+    }
+
+    match len {
+        3 => {
+            let [r, g, b, ..] = digits;
+            Ok((r * 17, g * 17, b * 17))
+        }
+        6 => {
+            let [r1, r2, g1, g2, b1, b2] = digits;
+            Ok((r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2))
+        }
+        _ => Err(HexColorError::InvalidLength(len)),
+    }
+}
@@ -0,0 +1,380 @@
+use alloc::{borrow::Cow, string::String, vec, vec::Vec};
+
+use unicode_bidi::{BidiInfo, Level};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use widestring::Utf32Str;
+
+use crate::{span::MessageSpan, strip_ansi, wrap::skip_escape};
+
+/// The marker shown where a line that is too wide is cut off.
+pub(crate) const ELLIPSIS: &str = "…";
+/// The marker used instead of [`ELLIPSIS`] in plain output, which is ASCII only.
+pub(crate) const PLAIN_ELLIPSIS: &str = "...";
+
+/// A grapheme cluster of a source line and the display columns it covers.
+#[derive(Debug, Clone)]
+pub(crate) struct Cluster {
+    pub(crate) text: String,
+    pub(crate) col_start: usize,
+    pub(crate) width: usize,
+}
+
+/// The columns of a line that are shown, in columns of the whole line.
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    start: usize,
+    end: usize,
+    marker: &'static str,
+}
+
+/// The layout of a source line in display columns, where every grapheme
+/// cluster takes up as many columns as it is wide (and always at least one),
+/// and tabs reach up to the next tab stop.
+///
+/// Lines containing right-to-left text are laid out in visual order, as
+/// resolved by the Unicode bidi algorithm with a left-to-right base direction,
+/// so that columns match where a character actually appears.
+///
+/// ANSI escape sequences can be kept in the clusters after them instead of
+/// taking up columns of their own, for code that is already colored.
+#[derive(Debug, Clone)]
+pub(crate) struct LineColumns {
+    /// The clusters in visual order.
+    clusters: Vec<Cluster>,
+    /// The index into `clusters` for each char of the line, in logical order.
+    char_clusters: Vec<usize>,
+    width: usize,
+    /// Whether the line has right-to-left text, in which case the visual
+    /// order differs from the logical one.
+    pub(crate) rtl: bool,
+    window: Option<Window>,
+}
+
+impl LineColumns {
+    pub(crate) fn new(line: &Utf32Str, tab_width: usize, ansi: bool) -> Self {
+        let line = line.to_string();
+
+        // each grapheme with the index of its first char, in logical order
+        let mut graphemes = vec![];
+        let mut char_count = 0;
+        for (byte_idx, text) in line.grapheme_indices(true) {
+            graphemes.push((byte_idx, char_count, text));
+            char_count += text.chars().count();
+        }
+        if ansi {
+            graphemes = attach_escapes(&line, graphemes);
+        }
+
+        let bidi = BidiInfo::new(&line, Some(Level::ltr()));
+        let rtl = bidi.has_rtl();
+        if rtl {
+            graphemes = visual_order(&bidi, graphemes);
+        }
+
+        let mut clusters = vec![];
+        let mut char_clusters = vec![0; char_count];
+        let mut col = 0;
+
+        for (_, char_idx, text) in graphemes {
+            let visible = match ansi {
+                true => Cow::Owned(strip_ansi(text)),
+                false => Cow::Borrowed(text),
+            };
+            let width = match visible.as_ref() {
+                "\t" => tab_width.max(1) - col % tab_width.max(1),
+                visible => visible.width().max(1),
+            };
+
+            char_clusters[char_idx..char_idx + text.chars().count()].fill(clusters.len());
+            clusters.push(Cluster {
+                text: text.into(),
+                col_start: col,
+                width,
+            });
+            col += width;
+        }
+
+        Self {
+            clusters,
+            char_clusters,
+            width: col,
+            rtl,
+            window: None,
+        }
+    }
+
+    /// Shows only the part of the line around `focus` (spans in columns of the
+    /// whole line) if it's wider than `max_width`, with `marker` in place of
+    /// the parts that are cut off.
+    ///
+    /// At least one column of the line is shown, so with the markers on both
+    /// sides the shown part can be wider than a `max_width` that is too small
+    /// to fit them.
+    ///
+    /// All columns returned afterwards are relative to the shown part, where
+    /// spans that are cut off entirely point at the marker on their side.
+    pub(crate) fn fit(&mut self, max_width: usize, focus: &[MessageSpan], marker: &'static str) {
+        if self.width <= max_width || focus.is_empty() {
+            return;
+        }
+        let lo = focus.iter().map(|s| s.start).min().unwrap();
+        let hi = focus.iter().map(|s| s.end).max().unwrap();
+        // center on everything if it fits, or else just on the first span
+        let center = if hi - lo <= max_width {
+            (lo + hi) / 2
+        } else {
+            let first = focus.iter().min_by_key(|s| s.start).unwrap();
+            first.start + first.size() / 2
+        };
+
+        let marker_width = marker.width();
+        let content = max_width.saturating_sub(2 * marker_width).max(1);
+        let mut start = center.saturating_sub(content / 2).min(self.width - content);
+        let mut end = start + content;
+        // a side that isn't cut off has no marker, leaving its columns to the line
+        if start == 0 {
+            end = (end + marker_width).min(self.width);
+        }
+        if end == self.width {
+            start = start.saturating_sub(marker_width);
+        }
+        self.window = Some(Window { start, end, marker });
+    }
+
+    /// Returns the shown clusters, in visual order.
+    pub(crate) fn shown_clusters(&self) -> Cow<'_, [Cluster]> {
+        let Some(window) = self.window else {
+            return Cow::Borrowed(&self.clusters);
+        };
+        let left = self.left_marker_len();
+        let marker = |col_start| Cluster {
+            text: window.marker.into(),
+            col_start,
+            width: window.marker.width(),
+        };
+
+        let mut shown = vec![];
+        if left > 0 {
+            shown.push(marker(0));
+        }
+        for cluster in &self.clusters {
+            let cluster_end = cluster.col_start + cluster.width;
+            if cluster_end <= window.start || cluster.col_start >= window.end {
+                continue;
+            }
+            if cluster.col_start >= window.start && cluster_end <= window.end {
+                shown.push(Cluster {
+                    col_start: cluster.col_start - window.start + left,
+                    ..cluster.clone()
+                });
+            } else {
+                // a wide cluster that is cut in half is left blank
+                for col in cluster.col_start.max(window.start)..cluster_end.min(window.end) {
+                    shown.push(Cluster {
+                        text: " ".into(),
+                        col_start: col - window.start + left,
+                        width: 1,
+                    });
+                }
+            }
+        }
+        if window.end < self.width {
+            shown.push(marker(window.end - window.start + left));
+        }
+        Cow::Owned(shown)
+    }
+
+    fn left_marker_len(&self) -> usize {
+        match self.window {
+            Some(window) if window.start > 0 => window.marker.width(),
+            _ => 0,
+        }
+    }
+
+    /// Maps a column of the whole line where something starts to the shown part.
+    fn map_start(&self, col: usize) -> usize {
+        let Some(window) = self.window else {
+            return col;
+        };
+        let left = self.left_marker_len();
+        if col < window.start {
+            0
+        } else if col >= window.end && window.end < self.width {
+            window.end - window.start + left
+        } else {
+            col - window.start + left
+        }
+    }
+
+    /// Maps a column of the whole line where something ends to the shown part.
+    fn map_end(&self, col: usize) -> usize {
+        let Some(window) = self.window else {
+            return col;
+        };
+        let left = self.left_marker_len();
+        if col <= window.start {
+            left
+        } else if col > window.end && window.end < self.width {
+            window.end - window.start + left + window.marker.width()
+        } else {
+            col - window.start + left
+        }
+    }
+
+    fn raw_col(&self, idx: usize) -> usize {
+        match self.char_clusters.get(idx) {
+            Some(&c) => self.clusters[c].col_start,
+            None => self.width + (idx - self.char_clusters.len()),
+        }
+    }
+
+    /// Returns the column just after the char at index `idx`.
+    fn raw_char_end_col(&self, idx: usize) -> usize {
+        match self.char_clusters.get(idx) {
+            Some(&c) => self.clusters[c].col_start + self.clusters[c].width,
+            None => self.raw_col(idx) + 1,
+        }
+    }
+
+    fn raw_end_col(&self, idx: usize) -> usize {
+        if self.rtl && idx > 0 {
+            return (0..idx).map(|i| self.raw_char_end_col(i)).max().unwrap();
+        }
+        match idx.checked_sub(1) {
+            Some(i) => self.raw_char_end_col(i),
+            None => 0,
+        }
+    }
+
+    /// Returns the column that the char at index `idx` starts at.
+    ///
+    /// Chars inside a grapheme cluster map to the start of the cluster, and
+    /// indices past the end of the line continue one column per char.
+    pub(crate) fn col(&self, idx: usize) -> usize {
+        self.map_start(self.raw_col(idx))
+    }
+
+    /// Returns the column just after the char at index `idx - 1`, so that a
+    /// span ending inside a grapheme cluster still covers the whole cluster.
+    ///
+    /// On lines with right-to-left text this is the furthest column reached by
+    /// any of the first `idx` chars.
+    pub(crate) fn end_col(&self, idx: usize) -> usize {
+        self.map_end(self.raw_end_col(idx))
+    }
+
+    /// Maps a char-aligned span relative to the start of the line to display columns.
+    ///
+    /// On lines with right-to-left text the chars of a span may not be next to
+    /// each other visually, so the returned span covers all of them.
+    pub(crate) fn span(&self, span: MessageSpan) -> MessageSpan {
+        let (start, end) = if self.rtl && span.size() > 0 {
+            (
+                (span.start..span.end)
+                    .map(|i| self.raw_col(i))
+                    .min()
+                    .unwrap(),
+                (span.start..span.end)
+                    .map(|i| self.raw_char_end_col(i))
+                    .max()
+                    .unwrap(),
+            )
+        } else {
+            (self.raw_col(span.start), self.raw_end_col(span.end))
+        };
+        let start = self.map_start(start);
+        MessageSpan {
+            start,
+            end: self.map_end(end).max(start),
+        }
+    }
+
+    /// Maps a span of display columns of the whole line back to chars relative
+    /// to the start of the line, covering every char that is at least partly
+    /// inside it. Columns past the end of the line continue one char per column.
+    pub(crate) fn char_span(&self, cols: MessageSpan) -> MessageSpan {
+        let len = self.char_clusters.len();
+        let past_end = |col: usize| len + (col - self.width);
+
+        // an empty span still picks out the char at its column
+        let mut covered = (0..len).filter(|&i| {
+            let cluster = &self.clusters[self.char_clusters[i]];
+            cluster.col_start < cols.end.max(cols.start + 1)
+                && cluster.col_start + cluster.width > cols.start
+        });
+        let Some(first) = covered.next() else {
+            return MessageSpan {
+                start: past_end(cols.start.max(self.width)),
+                end: past_end(cols.end.max(self.width)),
+            };
+        };
+        let end = if cols.size() == 0 {
+            first
+        } else if cols.end > self.width {
+            past_end(cols.end)
+        } else {
+            covered.next_back().unwrap_or(first) + 1
+        };
+        MessageSpan { start: first, end }
+    }
+}
+
+/// Joins every ANSI escape sequence of a line (given as graphemes with their
+/// byte and char index) to the grapheme after it, or to the last one if it
+/// ends the line, so it is printed along with that grapheme.
+fn attach_escapes<'t>(
+    line: &'t str,
+    graphemes: Vec<(usize, usize, &'t str)>,
+) -> Vec<(usize, usize, &'t str)> {
+    let mut out: Vec<(usize, usize, &'t str)> = Vec::with_capacity(graphemes.len());
+    // the byte and char index of the first escape waiting for a grapheme
+    let mut pending = None;
+    let mut escape_end = 0;
+
+    for (byte_idx, char_idx, text) in graphemes {
+        if byte_idx < escape_end || text == "\x1b" {
+            if byte_idx >= escape_end {
+                let mut chars = line[byte_idx + 1..].chars();
+                skip_escape(&mut chars);
+                escape_end = line.len() - chars.as_str().len();
+            }
+            pending.get_or_insert((byte_idx, char_idx));
+            continue;
+        }
+        let (start, char_start) = pending.take().unwrap_or((byte_idx, char_idx));
+        out.push((start, char_start, &line[start..byte_idx + text.len()]));
+    }
+    if let Some((start, char_start)) = pending {
+        match out.last_mut() {
+            Some(last) => last.2 = &line[last.0..],
+            None => out.push((start, char_start, &line[start..])),
+        }
+    }
+    out
+}
+
+/// Reorders the graphemes of a line (given with their byte and char index)
+/// from logical to visual order, reversing the ones in right-to-left runs.
+fn visual_order<'t>(
+    bidi: &BidiInfo,
+    graphemes: Vec<(usize, usize, &'t str)>,
+) -> Vec<(usize, usize, &'t str)> {
+    let mut out = Vec::with_capacity(graphemes.len());
+
+    for para in &bidi.paragraphs {
+        let (levels, runs) = bidi.visual_runs(para, para.range.clone());
+        for run in runs {
+            let run_graphemes = graphemes
+                .iter()
+                .filter(|(byte_idx, _, _)| run.contains(byte_idx))
+                .copied();
+            if levels[run.start].is_rtl() {
+                out.extend(run_graphemes.rev());
+            } else {
+                out.extend(run_graphemes);
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,256 @@
+//! A fluent builder in the shape of [`ariadne`](https://docs.rs/ariadne)'s,
+//! so that moving over from it is mostly a matter of changing imports.
+//!
+//! Only the common path is covered, and the source is given straight to the
+//! printing methods instead of through a cache:
+//!
+//! | `ariadne`                             | here                                  |
+//! |---------------------------------------|---------------------------------------|
+//! | `Report::build(kind, span)`           | [`Report::build`]`(kind)`             |
+//! | `Label::new(span)`                    | [`Label::new`]`(span)`, in chars      |
+//! | `Label::with_color(Color::Red)`       | [`Label::with_color`]`((255, 0, 0))`  |
+//! | `Label::with_order(n)`                | [`Label::with_priority`]`(n)`         |
+//! | `ReportKind::Advice`                  | [`ReportKind::Advice`], a [`Severity::Note`] |
+//! | `with_config(config)`                 | [`ReportBuilder::with_theme`]         |
+//! | `print((id, Source::from(src)))`      | [`Report::print`]`(src)`              |
+//!
+//! Labels become [`Message`]s, and labels without a color get the color of
+//! the kind of report.
+//!
+//! ```
+//! use lyneate::fluent::{Label, Report, ReportKind};
+//!
+//! let report = Report::build(ReportKind::Error)
+//!     .with_code("E03")
+//!     .with_message("Incompatible types")
+//!     .with_label(Label::new(13..19).with_message("This is a string"))
+//!     .with_label(Label::new(7..10).with_message("This is a `u32`").with_color((64, 159, 255)))
+//!     .with_note("Strings can't be turned into numbers implicitly")
+//!     .finish();
+//! let display = lyneate::strip_ansi(&report.display_str("let x: u32 = \"five\";"));
+//!
+//! assert!(display.starts_with("[E03] Error: Incompatible types\n"));
+//! assert!(display.contains("This is a string"));
+//! assert!(display.ends_with("Note: Strings can't be turned into numbers implicitly"));
+//! ```
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::ops::Range;
+
+use crate::{ansi::truecolor, report::Color, Message, Severity, Theme};
+
+/// The kind of a report, which is shown at the start of its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReportKind {
+    Error,
+    Warning,
+    Advice,
+}
+
+impl ReportKind {
+    /// Returns the severity of the report kind.
+    pub fn severity(self) -> Severity {
+        match self {
+            ReportKind::Error => Severity::Error,
+            ReportKind::Warning => Severity::Warning,
+            ReportKind::Advice => Severity::Note,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ReportKind::Error => "Error",
+            ReportKind::Warning => "Warning",
+            ReportKind::Advice => "Advice",
+        }
+    }
+}
+
+/// A label pointing at a char span of the source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Label {
+    span: Range<usize>,
+    message: String,
+    color: Option<Color>,
+    priority: i32,
+}
+
+impl Label {
+    /// Creates a new label without any text, for the chars in `span`.
+    pub fn new(span: Range<usize>) -> Self {
+        Self {
+            span,
+            message: String::new(),
+            color: None,
+            priority: 0,
+        }
+    }
+
+    pub fn with_message(mut self, message: impl ToString) -> Self {
+        self.message = message.to_string();
+        self
+    }
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+    /// See [`Message::priority`].
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// A builder for a [`Report`], created by [`Report::build`].
+#[derive(Debug, Clone)]
+pub struct ReportBuilder {
+    report: Report,
+}
+
+impl ReportBuilder {
+    pub fn with_code(mut self, code: impl ToString) -> Self {
+        self.report.code = Some(code.to_string());
+        self
+    }
+    pub fn with_message(mut self, message: impl ToString) -> Self {
+        self.report.message = Some(message.to_string());
+        self
+    }
+    /// Sets the name of the source, shown above the code.
+    pub fn with_source_name(mut self, name: impl ToString) -> Self {
+        self.report.source_name = Some(name.to_string());
+        self
+    }
+    pub fn with_label(mut self, label: Label) -> Self {
+        self.report.labels.push(label);
+        self
+    }
+    pub fn with_labels(mut self, labels: impl IntoIterator<Item = Label>) -> Self {
+        self.report.labels.extend(labels);
+        self
+    }
+    pub fn with_note(mut self, note: impl ToString) -> Self {
+        self.report.notes.push(("Note", note.to_string()));
+        self
+    }
+    pub fn with_help(mut self, help: impl ToString) -> Self {
+        self.report.notes.push(("Help", help.to_string()));
+        self
+    }
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.report.theme = theme;
+        self
+    }
+
+    pub fn finish(self) -> Report {
+        self.report
+    }
+}
+
+/// A report with a header and notes around the code, built with
+/// [`Report::build`].
+#[derive(Debug, Clone)]
+pub struct Report {
+    kind: ReportKind,
+    code: Option<String>,
+    message: Option<String>,
+    source_name: Option<String>,
+    labels: Vec<Label>,
+    notes: Vec<(&'static str, String)>,
+    theme: Theme,
+}
+
+impl Report {
+    /// Starts building a report of the given kind.
+    pub fn build(kind: ReportKind) -> ReportBuilder {
+        ReportBuilder {
+            report: Report {
+                kind,
+                code: None,
+                message: None,
+                source_name: None,
+                labels: Vec::new(),
+                notes: Vec::new(),
+                theme: Theme::default(),
+            },
+        }
+    }
+
+    /// Returns the lyneate report for the code in `source`, without the header
+    /// and notes.
+    pub fn code_report<'a>(&'a self, source: &str) -> crate::Report<'a> {
+        let color = self.kind.severity().color();
+        let report = crate::Report::new_char_spanned(
+            source,
+            self.labels.iter().map(|label| {
+                Message::new(
+                    label.span.clone(),
+                    label.message.as_str(),
+                    label.color.unwrap_or(color),
+                )
+                .with_priority(label.priority)
+            }),
+        )
+        .with_theme(self.theme.clone());
+
+        match &self.source_name {
+            Some(name) => report.with_source_name(name.as_str()),
+            None => report,
+        }
+    }
+
+    /// Returns an iterator over the rows of the report display for the code in
+    /// `source`, each fully rendered and without a trailing newline. Like with
+    /// [`Report::rows`](crate::Report::rows), the code is laid out up front and
+    /// only the rows are rendered as they are reached.
+    pub fn rows(&self, source: &str) -> impl Iterator<Item = String> + '_ {
+        let kind = truecolor(self.kind.name(), self.kind.severity().color());
+        let header = match (&self.code, &self.message) {
+            (Some(code), Some(message)) => format!("[{code}] {kind}: {message}"),
+            (Some(code), None) => format!("[{code}] {kind}"),
+            (None, Some(message)) => format!("{kind}: {message}"),
+            (None, None) => kind,
+        };
+        let notes = self
+            .notes
+            .iter()
+            .map(|(name, text)| format!("{name}: {text}"));
+
+        core::iter::once(header)
+            .chain(self.code_report(source).rows())
+            .chain(notes)
+    }
+
+    /// Returns the string of the report display for the code in `source`.
+    pub fn display_str(&self, source: &str) -> String {
+        self.rows(source).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Prints the report for the code in `source` to stdout.
+    #[cfg(feature = "std")]
+    pub fn print(&self, source: &str) {
+        for row in self.rows(source) {
+            println!("{}", row)
+        }
+    }
+    /// Prints the report for the code in `source` to stderr.
+    #[cfg(feature = "std")]
+    pub fn eprint(&self, source: &str) {
+        for row in self.rows(source) {
+            eprintln!("{}", row)
+        }
+    }
+    /// Writes the report for the code in `source` to `w`, with a newline after
+    /// every row.
+    #[cfg(feature = "std")]
+    pub fn write(&self, source: &str, w: &mut impl std::io::Write) -> std::io::Result<()> {
+        for row in self.rows(source) {
+            writeln!(w, "{}", row)?;
+        }
+        Ok(())
+    }
+}
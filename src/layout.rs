@@ -0,0 +1,56 @@
+use alloc::{string::String, vec::Vec};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{ansi::strip_ansi, report::Color};
+
+/// A row of a laid out report display, as returned by
+/// [`Report::layout`](crate::Report::layout).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LayoutRow {
+    /// The 0-based index of the source line shown in this row, if any.
+    pub line: Option<usize>,
+    /// The cells of the code area, starting with the room left for the side
+    /// bars of multiline messages.
+    pub cells: Vec<LayoutCell>,
+    /// The label text that follows the cells.
+    pub label: Option<String>,
+}
+
+/// A single column of the code area of a report display.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LayoutCell {
+    /// What is shown in the cell. This is empty for the cells covered by a
+    /// preceding wide character.
+    pub text: String,
+    /// The color the cell is highlighted with, or `None` for unhighlighted
+    /// cells, which get [`ThemeEffects::unhighlighted`](crate::ThemeEffects::unhighlighted).
+    pub color: Option<Color>,
+}
+
+impl LayoutRow {
+    /// Returns the cells of the row followed by a cell for every column of its
+    /// label, if any, after a blank one. Escape sequences in the label are
+    /// left out, and like in the code area, a wide character is followed by
+    /// empty cells for the rest of its columns.
+    pub fn cells_with_label(&self) -> Vec<LayoutCell> {
+        let mut cells = self.cells.clone();
+        let Some(label) = &self.label else {
+            return cells;
+        };
+        let blank = |text: &str| LayoutCell {
+            text: text.into(),
+            color: None,
+        };
+
+        cells.push(blank(" "));
+        for grapheme in strip_ansi(label).graphemes(true) {
+            cells.push(blank(grapheme));
+            for _ in 1..grapheme.width() {
+                cells.push(blank(""));
+            }
+        }
+        cells
+    }
+}
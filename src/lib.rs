@@ -1,8 +1,34 @@
-#![doc = include_str!("../README.md")]
+// the README example prints with `Report::display`, which needs `std`
+#![cfg_attr(feature = "std", doc = include_str!("../README.md"))]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(feature = "annotate-snippets")]
+mod annotate_snippets;
+mod ansi;
+mod color;
+mod columns;
+pub mod fluent;
+mod layout;
+mod lines;
+mod message;
+#[cfg(feature = "miette")]
+mod miette;
+mod multi;
+mod palette;
+pub mod prelude;
 mod report;
 mod span;
 mod theme;
+mod wrap;
 
-pub use report::Report;
+pub use ansi::strip_ansi;
+pub use color::{color_from_hex, HexColorError};
+pub use layout::{LayoutCell, LayoutRow};
+pub use message::{Message, Severity};
+pub use multi::MultiReport;
+pub use palette::palette_color_blind_safe;
+pub use report::{Color, Report};
+pub use span::{byte_span_to_char_span, char_span_to_byte_span};
 pub use theme::*;
@@ -0,0 +1,107 @@
+use alloc::vec::Vec;
+
+use widestring::Utf32Str;
+
+/// The lines of some code, found by indexing where each one starts so that
+/// only the lines messages actually refer to ever get sliced out of it.
+///
+/// A leading byte order mark is left out of the first line, so offsets still
+/// count it but columns start at the first real char.
+///
+/// Lines end at every `\n`, and with `unicode_separators` also at every line
+/// or paragraph separator (U+2028 and U+2029).
+#[derive(Debug, Clone)]
+pub(crate) struct Lines<'c> {
+    code: &'c Utf32Str,
+    starts: Vec<usize>,
+    unicode_separators: bool,
+}
+
+impl<'c> Lines<'c> {
+    pub(crate) fn new(code: &'c Utf32Str, unicode_separators: bool) -> Self {
+        let bom = usize::from(code.as_char_slice().first() == Some(&'\u{FEFF}'));
+        let starts = core::iter::once(bom)
+            .chain(
+                code.as_char_slice()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| match c {
+                        '\n' => true,
+                        '\u{2028}' | '\u{2029}' => unicode_separators,
+                        _ => false,
+                    })
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+
+        Self {
+            code,
+            starts,
+            unicode_separators,
+        }
+    }
+
+    /// Returns whether `c` is part of a line break, which is left out of the
+    /// content of a line.
+    pub(crate) fn is_break(&self, c: char) -> bool {
+        match c {
+            '\n' | '\r' => true,
+            '\u{2028}' | '\u{2029}' => self.unicode_separators,
+            _ => false,
+        }
+    }
+
+    /// Returns the length of the line without its line break.
+    pub(crate) fn content_len(&self, line: usize) -> usize {
+        self.line(line)
+            .as_char_slice()
+            .iter()
+            .rposition(|c| !self.is_break(*c))
+            .map_or(0, |i| i + 1)
+    }
+
+    /// Returns the number of lines, which is always at least one.
+    pub(crate) fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Returns the index of the line containing the char at `idx`.
+    ///
+    /// Offsets past the end of the code, like one right after a trailing
+    /// newline, belong to the last (possibly empty) line.
+    pub(crate) fn line_of(&self, idx: usize) -> usize {
+        self.starts.partition_point(|start| *start <= idx) - 1
+    }
+
+    /// Returns the line that the span from `start` to `end` ends on and the
+    /// offset it ends at there, leaving out line breaks at the end of the span.
+    /// A span ending with the line break of a line, like one covering a whole
+    /// blank line, ends on that line rather than at the start of the next one.
+    pub(crate) fn span_end(&self, start: usize, end: usize) -> (usize, usize) {
+        let line = self.line_of(end.saturating_sub(1).max(start));
+
+        // offsets past the end of the code are kept as they are
+        let end = match end <= self.starts[line] + self.line(line).len() {
+            true => end
+                .min(self.starts[line] + self.content_len(line))
+                .max(start),
+            false => end,
+        };
+        (line, end)
+    }
+
+    /// Returns the char offset the line starts at.
+    pub(crate) fn start(&self, line: usize) -> usize {
+        self.starts[line]
+    }
+
+    /// Returns the text of the line, including its line break.
+    pub(crate) fn line(&self, line: usize) -> &'c Utf32Str {
+        let end = self
+            .starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.code.len());
+        &self.code[self.starts[line]..end]
+    }
+}
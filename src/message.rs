@@ -0,0 +1,278 @@
+use alloc::{borrow::Cow, vec, vec::Vec};
+use core::ops::Range;
+
+use crate::report::Color;
+
+/// A message pointing at a span of the code, in the units the report is
+/// created with.
+///
+/// Reports take anything that converts into a message, so plain
+/// `(span, text, color)` tuples can be used when the defaults are fine.
+///
+/// ```
+/// # use lyneate::{Message, Report};
+/// let report = Report::new_char_spanned(
+///     "let value = 5;",
+///     [
+///         Message::new(0..9, "declaration", (255, 159, 64)).with_priority(1),
+///         Message::new(4..14, "overlapping", (64, 159, 255)),
+///     ],
+/// );
+/// ```
+///
+/// Single-line spans of a message with empty text are only underlined, without
+/// an arm or label:
+///
+/// ```
+/// # use lyneate::Report;
+/// let report = Report::new_char_spanned("let value = 5;", [(4..9, "", (255, 64, 112))]);
+///
+/// assert_eq!(report.render_plain(), "   1.  let value = 5; \n           ----- ");
+/// ```
+///
+/// Messages on the exact same span share one underline and connector, with
+/// their labels stacked along it:
+///
+/// ```
+/// # use lyneate::Report;
+/// let report = Report::new_char_spanned(
+///     "let x = first + second;",
+///     [(8..13, "unused", (255, 191, 64)), (8..13, "shadowed", (255, 64, 112))],
+/// );
+///
+/// assert_eq!(report.render_plain().lines().collect::<Vec<_>>(), [
+///     "   1.  let x = first + second; ",
+///     "               ----- ",
+///     "                 | ",
+///     "                 |-- shadowed",
+///     "                 | ",
+///     "                 \\-- unused",
+/// ]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Message<'a> {
+    /// The span the message points at. An inverted span, with its end before
+    /// its start, points at the same part of the code as the one with its ends
+    /// swapped.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let code = "let value = first + second;";
+    /// let inverted = Report::new_char_spanned(code, [(15..10, "here", (255, 64, 112))]);
+    /// let report = Report::new_char_spanned(code, [(10..15, "here", (255, 64, 112))]);
+    ///
+    /// assert_eq!(inverted.render_plain(), report.render_plain());
+    /// ```
+    pub span: Range<usize>,
+    pub text: Cow<'a, str>,
+    /// The color of the underline, arm and label. Messages without one take
+    /// the color of their [severity](Message::severity), or else the
+    /// [default color](crate::Report::with_default_color) of the report.
+    pub color: Option<Color>,
+    /// Overlapping single-line messages are drawn in order of priority, so the
+    /// underline of the one with the highest priority stays intact. Messages of
+    /// equal priority are drawn in the [label order](crate::ThemeOptions::label_order),
+    /// by default that of their span start, then span end.
+    ///
+    /// Messages drawn later also get their labels placed further from the code.
+    pub priority: i32,
+    /// Text to replace the span with, shown in green in a row of its own
+    /// beneath the labels of the line, starting at the column of the span, so
+    /// it never covers the connectors of other messages. Only shown for
+    /// single-line messages.
+    pub suggestion: Option<Cow<'a, str>>,
+    /// More spans sharing the label of this message. The spans on a line get
+    /// their arms joined into one, and the label is only shown once, on the
+    /// last line with any of them. Spans covering several lines share one side
+    /// bar, which runs on dotted between them, and one label, however far apart
+    /// they are.
+    ///
+    /// ```
+    /// # use lyneate::{Message, Report};
+    /// let report = Report::new_char_spanned(
+    ///     "(ab, ab, cd, ab)",
+    ///     [Message::new(1..3, "these must match", (255, 64, 112))
+    ///         .with_extra_span(5..7)
+    ///         .with_extra_span(13..15)],
+    /// );
+    /// let plain = report.render_plain();
+    ///
+    /// assert_eq!(plain.matches("these must match").count(), 1);
+    /// assert!(plain.contains("--  --      --"));
+    /// ```
+    ///
+    /// ```
+    /// # use lyneate::{Message, Report};
+    /// let report = Report::new_char_spanned(
+    ///     "fn a() {\n}\n\nfn b() {\n}",
+    ///     [Message::new(0..10, "these two blocks conflict", (255, 64, 112)).with_extra_span(12..22)],
+    /// );
+    ///
+    /// assert_eq!(report.render_plain().lines().collect::<Vec<_>>(), [
+    ///     "   1.  /-> fn a() { ",
+    ///     "   2.  |-> } ",
+    ///     "       : ",
+    ///     "   4.  |-> fn b() { ",
+    ///     "   5.  |-> } ",
+    ///     "       | ",
+    ///     "       \\-- these two blocks conflict",
+    /// ]);
+    /// ```
+    pub extra_spans: Vec<Range<usize>>,
+    /// Overrides [`ThemeChars::underline`](crate::ThemeChars::underline) (or
+    /// the caret, with [`UnderlineStyle::Caret`](crate::UnderlineStyle::Caret))
+    /// for the single-line spans of this message, so one report can mix
+    /// underline styles.
+    ///
+    /// ```
+    /// # use lyneate::{Message, Report};
+    /// let report = Report::new_char_spanned(
+    ///     "let value = 5;",
+    ///     [
+    ///         Message::new(0..3, "error", (255, 64, 112)),
+    ///         Message::new(4..9, "warning", (255, 191, 64))
+    ///             .with_underline('~')
+    ///             .with_underline_junction('~'),
+    ///     ],
+    /// );
+    /// let display = lyneate::strip_ansi(&report.display_str());
+    ///
+    /// assert!(display.lines().nth(1).unwrap().contains("─┬─ ~~~~~"));
+    /// ```
+    pub underline: Option<char>,
+    /// Overrides the junction where the arm leaves the underline, which is
+    /// [`ThemeChars::underline_junction`](crate::ThemeChars::underline_junction)
+    /// or [`ThemeChars::underline_junction_up`](crate::ThemeChars::underline_junction_up)
+    /// depending on the arm direction.
+    pub underline_junction: Option<char>,
+    /// Draws the code the message highlights in reverse video, with its color
+    /// as the background, to make the most important message stand out.
+    ///
+    /// ```
+    /// # use lyneate::{Message, Report};
+    /// colored::control::set_override(true);
+    /// let report = Report::new_char_spanned(
+    ///     "let a = b;",
+    ///     [
+    ///         Message::new(4..5, "most important", (255, 64, 112)).with_reversed(true),
+    ///         Message::new(8..9, "less important", (64, 159, 255)),
+    ///     ],
+    /// );
+    /// let display = report.display_str();
+    /// let code_row = display.lines().next().unwrap();
+    ///
+    /// // the SGR code 7 turns on reverse video
+    /// assert_eq!(code_row.matches("\x1b[7;").count(), 1);
+    /// let reversed_from = code_row.find("\x1b[7;").unwrap();
+    /// assert!(lyneate::strip_ansi(&code_row[reversed_from..]).starts_with("a ="));
+    /// ```
+    pub reversed: bool,
+    /// How severe the problem this message points out is, counted in the
+    /// [summary](crate::Report::with_summary) below the report.
+    pub severity: Option<Severity>,
+}
+
+impl<'a> Message<'a> {
+    /// Creates a new message with the default priority of 0.
+    pub fn new(span: Range<usize>, text: impl Into<Cow<'a, str>>, color: Color) -> Self {
+        Self {
+            color: Some(color),
+            ..Self::uncolored(span, text)
+        }
+    }
+    /// Creates a new message without a color of its own.
+    pub fn uncolored(span: Range<usize>, text: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            span,
+            text: text.into(),
+            color: None,
+            priority: 0,
+            suggestion: None,
+            extra_spans: vec![],
+            underline: None,
+            underline_junction: None,
+            reversed: false,
+            severity: None,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+    pub fn with_suggestion(mut self, suggestion: impl Into<Cow<'a, str>>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+    pub fn with_extra_span(mut self, span: Range<usize>) -> Self {
+        self.extra_spans.push(span);
+        self
+    }
+    pub fn with_underline(mut self, underline: char) -> Self {
+        self.underline = Some(underline);
+        self
+    }
+    pub fn with_underline_junction(mut self, junction: char) -> Self {
+        self.underline_junction = Some(junction);
+        self
+    }
+    pub fn with_reversed(mut self, reversed: bool) -> Self {
+        self.reversed = reversed;
+        self
+    }
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = Some(severity);
+        self
+    }
+}
+
+impl<'a, M> From<(Range<usize>, M, Color)> for Message<'a>
+where
+    M: Into<Cow<'a, str>>,
+{
+    fn from((span, text, color): (Range<usize>, M, Color)) -> Self {
+        Self::new(span, text, color)
+    }
+}
+
+impl<'a, M> From<(Range<usize>, M)> for Message<'a>
+where
+    M: Into<Cow<'a, str>>,
+{
+    fn from((span, text): (Range<usize>, M)) -> Self {
+        Self::uncolored(span, text)
+    }
+}
+
+/// How severe the problem a message points out is, ordered from the least to
+/// the most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// Returns the color the severity is shown in, which is also a good color
+    /// for its messages.
+    pub fn color(self) -> Color {
+        match self {
+            Severity::Note => (64, 159, 255),
+            Severity::Warning => (255, 191, 64),
+            Severity::Error => (255, 64, 112),
+        }
+    }
+
+    /// Returns what to call `count` messages of the severity.
+    pub(crate) fn name(self, count: usize) -> &'static str {
+        match (self, count) {
+            (Severity::Note, 1) => "note",
+            (Severity::Note, _) => "notes",
+            (Severity::Warning, 1) => "warning",
+            (Severity::Warning, _) => "warnings",
+            (Severity::Error, 1) => "error",
+            (Severity::Error, _) => "errors",
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use alloc::{borrow::Cow, vec::Vec};
+
+use miette::Diagnostic;
+
+use crate::{Message, Report, Severity};
+
+impl<'a> Report<'a> {
+    /// Creates a report from the labels of a [`miette`] diagnostic, with their
+    /// spans as byte offsets into `source`.
+    ///
+    /// Primary labels, or all of them if none is marked primary, get the
+    /// severity of the diagnostic and are colored by it, so they are counted in
+    /// the [summary](Report::with_summary). The other labels are colored from
+    /// the palette (see [`Report::add_message_auto`]).
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// # use miette::{Diagnostic, LabeledSpan};
+    /// #[derive(Debug)]
+    /// struct UnknownName;
+    ///
+    /// impl std::fmt::Display for UnknownName {
+    ///     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    ///         f.write_str("unknown name")
+    ///     }
+    /// }
+    /// impl std::error::Error for UnknownName {}
+    /// impl Diagnostic for UnknownName {
+    ///     fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+    ///         Some(Box::new(
+    ///             [
+    ///                 LabeledSpan::new_primary_with_span(Some("not found".into()), (8..13)),
+    ///                 LabeledSpan::new_with_span(Some("did you mean this?".into()), (4..7)),
+    ///             ]
+    ///             .into_iter(),
+    ///         ))
+    ///     }
+    /// }
+    ///
+    /// let report = Report::from_miette(&UnknownName, "let foo = fooo;");
+    /// let plain = report.render_plain();
+    ///
+    /// assert!(plain.contains("not found"));
+    /// assert!(plain.contains("did you mean this?"));
+    /// ```
+    pub fn from_miette(diagnostic: &dyn Diagnostic, source: &'a str) -> Self {
+        let severity = match diagnostic.severity().unwrap_or_default() {
+            miette::Severity::Error => Severity::Error,
+            miette::Severity::Warning => Severity::Warning,
+            miette::Severity::Advice => Severity::Note,
+        };
+
+        let mut report = Report::new_byte_spanned(source, [] as [Message; 0]);
+        let Some(labels) = diagnostic.labels() else {
+            return report;
+        };
+        let labels = labels.collect::<Vec<_>>();
+        let any_primary = labels.iter().any(|l| l.primary());
+
+        for label in labels {
+            let span = label.offset()..label.offset() + label.len();
+            let text = Cow::Owned(label.label().unwrap_or_default().into());
+            if label.primary() || !any_primary {
+                report = report.with_messages([
+                    Message::new(span, text, severity.color()).with_severity(severity)
+                ]);
+            } else {
+                report.add_message_auto(span, text);
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, vec};
+    use core::fmt;
+
+    use miette::{Diagnostic, LabeledSpan, Severity};
+
+    use crate::Report;
+
+    #[derive(Debug)]
+    struct UnusedImport;
+
+    impl fmt::Display for UnusedImport {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("unused import")
+        }
+    }
+    impl std::error::Error for UnusedImport {}
+    impl Diagnostic for UnusedImport {
+        fn severity(&self) -> Option<Severity> {
+            Some(Severity::Advice)
+        }
+        fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+            Some(Box::new(
+                vec![
+                    LabeledSpan::new_primary_with_span(Some("not used".into()), 4..7),
+                    LabeledSpan::new_with_span(Some("imported here".into()), 0..3),
+                ]
+                .into_iter(),
+            ))
+        }
+    }
+
+    #[test]
+    fn advice_is_summarized_as_a_note() {
+        let plain = Report::from_miette(&UnusedImport, "use foo;").render_plain();
+
+        assert!(plain.ends_with("\n   1 note"), "{plain}");
+    }
+}
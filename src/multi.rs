@@ -0,0 +1,251 @@
+use alloc::{borrow::Cow, string::String, vec, vec::Vec};
+use core::{fmt::Display, ops::Range};
+
+use crate::{palette::palette_color, report::Color, Message, Report, Theme};
+
+/// A report over several source files, displayed one after another with a
+/// header naming each file.
+///
+/// Every section is laid out like a single [`Report`], with its own line number
+/// gutter, while sharing one theme. Messages keep the colors they are given, so
+/// related messages in different files can be tied together by giving them the
+/// same color.
+///
+/// ```
+/// # use lyneate::MultiReport;
+/// let report = MultiReport::new()
+///     .with_char_spanned("lib.rs", "pub fn f() {}", [(7..8, "defined here", (64, 159, 255))])
+///     .with_char_spanned("main.rs", "fn main() { f(1); }", [(12..16, "used here", (64, 159, 255))]);
+///
+/// let display = report.display_str();
+/// assert!(display.contains("lib.rs:1:8"));
+/// assert!(display.contains("main.rs:1:13"));
+/// ```
+///
+/// Messages colored automatically share one palette over all sections, so they
+/// get the same colors as if they were added to a single report (see
+/// [`MultiReport::add_message_auto`]).
+#[derive(Debug, Clone, Default)]
+pub struct MultiReport<'a> {
+    sections: Vec<Report<'a>>,
+    palette: Option<Vec<Color>>,
+    // the section of every automatically colored message, in the order they were added
+    auto_colored: Vec<usize>,
+    // copied into every section, so it's only changed through `set_theme`
+    theme: Theme,
+}
+
+impl<'a> MultiReport<'a> {
+    /// Creates a new report without any sections.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a section for a source file with byte-aligned spans.
+    pub fn with_byte_spanned<I>(
+        self,
+        name: impl Into<Cow<'a, str>>,
+        code: &'a str,
+        messages: I,
+    ) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Message<'a>>,
+    {
+        self.with_section(Report::new_byte_spanned(code, messages).with_source_name(name))
+    }
+    /// Adds a section for a source file with char-aligned spans.
+    pub fn with_char_spanned<I>(
+        self,
+        name: impl Into<Cow<'a, str>>,
+        code: &str,
+        messages: I,
+    ) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Message<'a>>,
+    {
+        self.with_section(Report::new_char_spanned(code, messages).with_source_name(name))
+    }
+    /// Adds an already built report as a section. Its theme is replaced by the
+    /// theme of this report, and its automatically colored messages take the
+    /// next colors of this report's palette.
+    pub fn with_section(mut self, report: Report<'a>) -> Self {
+        let section = self.sections.len();
+        self.auto_colored
+            .extend(core::iter::repeat_n(section, report.auto_colored_len()));
+        self.sections.push(report.with_theme(self.theme.clone()));
+        self.share_palette();
+        self
+    }
+
+    /// Adds a message to the `section`th section (in the order they were added)
+    /// with a span in the units that section was created with, colored with the
+    /// next color of the palette shared by all sections.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no `section`th section.
+    pub fn add_message_auto(
+        &mut self,
+        section: usize,
+        span: Range<usize>,
+        msg: impl Into<Cow<'a, str>>,
+    ) {
+        self.sections[section].add_message_auto(span, msg);
+        self.auto_colored.push(section);
+        self.share_palette();
+    }
+
+    /// Sets the colors cycled through by [`MultiReport::add_message_auto`] over
+    /// all sections, like [`Report::with_palette`].
+    pub fn with_palette(mut self, palette: Vec<Color>) -> Self {
+        self.palette = Some(palette).filter(|p| !p.is_empty());
+        self.share_palette();
+        self
+    }
+
+    /// Recolors the automatically colored messages of all sections, in the
+    /// order they were added to any of them.
+    fn share_palette(&mut self) {
+        let mut section_idx = vec![0; self.sections.len()];
+        for (i, &section) in self.auto_colored.iter().enumerate() {
+            let color = palette_color(self.palette.as_deref(), i);
+            self.sections[section].set_auto_color(section_idx[section], color);
+            section_idx[section] += 1;
+        }
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.set_theme(theme);
+        self
+    }
+    /// Replaces the theme of a report held by reference, like
+    /// [`MultiReport::with_theme`], including the theme of every section.
+    pub fn set_theme(&mut self, theme: Theme) {
+        for section in &mut self.sections {
+            section.set_theme(theme.clone());
+        }
+        self.theme = theme;
+    }
+    /// Returns the theme shared by all sections.
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Returns an iterator over the rows of the pretty report display, with
+    /// an empty row between sections. Each section is laid out once its first
+    /// row is reached, and its rows are rendered one at a time like with
+    /// [`Report::rows`].
+    pub fn rows(&self) -> impl Iterator<Item = String> + '_ {
+        self.sections
+            .iter()
+            .enumerate()
+            .flat_map(|(i, section)| (i > 0).then(String::new).into_iter().chain(section.rows()))
+    }
+
+    /// Returns the string of the pretty report display
+    pub fn display_str(&self) -> String {
+        self.rows().collect::<Vec<_>>().join("\n")
+    }
+
+    /// Prettily displays the code report.
+    #[cfg(feature = "std")]
+    pub fn display(&self) {
+        for row in self.rows() {
+            println!("{}", row)
+        }
+    }
+    /// Prettily displays the code report on stderr.
+    #[cfg(feature = "std")]
+    pub fn eprint_display(&self) {
+        for row in self.rows() {
+            eprintln!("{}", row)
+        }
+    }
+}
+
+/// Writes the same output as [`MultiReport::display_str`].
+impl Display for MultiReport<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.display_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::MultiReport;
+    use crate::{report::Color, Message, Report, Theme, ThemeChars};
+
+    const PALETTE: [Color; 4] = [
+        (255, 64, 112),
+        (64, 159, 255),
+        (207, 255, 64),
+        (255, 159, 64),
+    ];
+
+    fn underline_colors(report: &Report) -> Vec<Color> {
+        let mut colors = report.layout()[1]
+            .cells
+            .iter()
+            .filter_map(|c| c.color)
+            .collect::<Vec<_>>();
+        colors.dedup();
+        colors
+    }
+
+    #[test]
+    fn auto_colors_continue_across_sections() {
+        let mut report = MultiReport::new()
+            .with_char_spanned("lib.rs", "pub fn f() {}", [] as [Message; 0])
+            .with_char_spanned("main.rs", "fn main() { f(1); }", [] as [Message; 0])
+            .with_palette(PALETTE.to_vec());
+        report.add_message_auto(0, 7..8, "defined here");
+        report.add_message_auto(1, 12..13, "used here");
+        report.add_message_auto(1, 14..15, "argument");
+        report.add_message_auto(0, 0..3, "visibility");
+
+        // the colors are taken in the order the messages were added, over all sections
+        assert_eq!(
+            underline_colors(&report.sections[0]),
+            [PALETTE[3], PALETTE[0]]
+        );
+        assert_eq!(
+            underline_colors(&report.sections[1]),
+            [PALETTE[1], PALETTE[2]]
+        );
+    }
+
+    #[test]
+    fn theme_set_after_adding_sections_applies_to_them() {
+        let mut report = MultiReport::new()
+            .with_char_spanned("lib.rs", "fn f() {\n}", [(0..10, "body", PALETTE[0])])
+            .with_char_spanned("main.rs", "fn main() {\n}", [(0..13, "body", PALETTE[1])]);
+        report.set_theme(Theme {
+            chars: ThemeChars::ascii(),
+            ..Default::default()
+        });
+
+        let display = crate::strip_ansi(&report.display_str());
+        assert!(!display.contains('─'));
+        assert_eq!(display.matches("/->").count(), 2);
+    }
+
+    #[test]
+    fn added_sections_continue_the_palette() {
+        let mut lib = Report::new_char_spanned("pub fn f() {}", [] as [Message; 0]);
+        lib.add_message_auto(7..8, "defined here");
+        let mut main = Report::new_char_spanned("fn main() { f(1); }", [] as [Message; 0]);
+        main.add_message_auto(12..13, "used here");
+
+        let report = MultiReport::new()
+            .with_section(lib)
+            .with_section(main)
+            .with_palette(PALETTE.to_vec());
+
+        assert_eq!(underline_colors(&report.sections[0]), [PALETTE[0]]);
+        assert_eq!(underline_colors(&report.sections[1]), [PALETTE[1]]);
+    }
+}
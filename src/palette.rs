@@ -0,0 +1,78 @@
+use alloc::{vec, vec::Vec};
+
+use crate::report::Color;
+
+/// Returns the colors of the Okabe-Ito palette, which stay distinguishable
+/// with the common kinds of color blindness, for use with
+/// [`Report::with_palette`](crate::Report::with_palette).
+///
+/// Its black is left out so that every color shows on dark terminals, and the
+/// rest are ordered so that consecutive colors are easy to tell apart: orange,
+/// sky blue, bluish green, yellow, blue, vermillion and reddish purple.
+///
+/// ```
+/// # use lyneate::{palette_color_blind_safe, Message, Report};
+/// let mut report = Report::new_char_spanned("let value = 5;", [] as [Message; 0])
+///     .with_palette(palette_color_blind_safe());
+/// report.add_message_auto(0..3, "keyword");
+/// report.add_message_auto(4..9, "name");
+///
+/// let colors = report.layout()[1].cells.iter().filter_map(|c| c.color).collect::<Vec<_>>();
+/// assert_eq!(colors[0], palette_color_blind_safe()[0]);
+/// assert_eq!(colors[3], palette_color_blind_safe()[1]);
+/// ```
+pub fn palette_color_blind_safe() -> Vec<Color> {
+    vec![
+        (230, 159, 0),
+        (86, 180, 233),
+        (0, 158, 115),
+        (240, 228, 66),
+        (0, 114, 178),
+        (213, 94, 0),
+        (204, 121, 167),
+    ]
+}
+
+/// Returns the color for the `idx`th automatically colored message, cycling
+/// through `palette` if one is set.
+pub(crate) fn palette_color(palette: Option<&[Color]>, idx: usize) -> Color {
+    match palette {
+        Some(palette) => palette[idx % palette.len()],
+        None => golden_color(idx),
+    }
+}
+
+/// Returns the color for the `idx`th automatically colored message, with hues
+/// spaced by the golden ratio so that nearby indices are far apart.
+fn golden_color(idx: usize) -> Color {
+    const GOLDEN_RATIO_CONJUGATE: f64 = 0.618_033_988_749_895;
+
+    let hue = 0.1 + idx as f64 * GOLDEN_RATIO_CONJUGATE;
+    let hue = hue - floor(hue);
+    hsv_to_rgb(hue, 0.65, 1.0)
+}
+
+/// Converts a color from HSV, with every component in `0.0..=1.0`, to RGB.
+fn hsv_to_rgb(h: f64, s: f64, v: f64) -> Color {
+    let sector = floor(h * 6.0);
+    let f = h * 6.0 - sector;
+    let p = v * (1.0 - s);
+    let q = v * (1.0 - f * s);
+    let t = v * (1.0 - (1.0 - f) * s);
+
+    let (r, g, b) = match sector as u32 % 6 {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    };
+    let to_u8 = |c: f64| (c * 255.0 + 0.5) as u8;
+    (to_u8(r), to_u8(g), to_u8(b))
+}
+
+/// Rounds a non-negative number down, as `f64::floor` needs `std`.
+fn floor(x: f64) -> f64 {
+    x as u64 as f64
+}
@@ -0,0 +1,20 @@
+//! The types needed for most reports, to be imported all at once:
+//!
+//! ```
+//! use lyneate::prelude::*;
+//!
+//! let report = Report::new_char_spanned("let value = 5;", [Message::new(4..9, "here", (255, 64, 112))])
+//!     .with_theme(Theme {
+//!         chars: ThemeChars::ascii(),
+//!         ..Default::default()
+//!     });
+//! ```
+//!
+//! The [`fluent`](crate::fluent) builder is left out, as its `Report` would
+//! clash with the one here.
+
+pub use crate::{
+    color_from_hex, palette_color_blind_safe, strip_ansi, ArmDirection, Color, ConnectorAnchor,
+    GutterMode, LabelOrder, Message, MultiReport, MultilineLabel, Report, Severity, Theme,
+    ThemeChars, ThemeEffects, ThemeOptions, ThemeSizing, UnderlineStyle,
+};
@@ -1,111 +1,1250 @@
-use std::{collections::BTreeMap, ops::Range};
-
-use colored::Colorize;
-use widestring::{Utf32Str, Utf32String};
+use alloc::{
+    borrow::Cow,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::{cmp::Ordering, fmt::Display, ops::Range};
+use widestring::Utf32String;
 
 use crate::{
-    span::{byte_span_to_char_span, MessageSpan},
-    Theme,
+    ansi::{hyperlink, truecolor, truecolor_reversed},
+    columns::{LineColumns, ELLIPSIS, PLAIN_ELLIPSIS},
+    layout::{LayoutCell, LayoutRow},
+    lines::Lines,
+    palette::palette_color,
+    span::{byte_span_to_char_span, normalized, utf16_span_to_char_span, MessageSpan},
+    strip_ansi,
+    wrap::{terminal_width, visible_len, wrap},
+    ArmDirection, ConnectorAnchor, GutterMode, LabelOrder, Message, MultilineLabel, Severity,
+    Theme, ThemeChars, ThemeEffects, UnderlineStyle,
 };
 
-type Color = (u8, u8, u8);
+/// A 24-bit RGB color, which [`color_from_hex`](crate::color_from_hex) can parse from hex.
+pub type Color = (u8, u8, u8);
+
+const SUGGESTION_COLOR: Color = (64, 224, 96);
+// for messages without a color when the report has no default color either
+const FALLBACK_COLOR: Color = (255, 255, 255);
 
 /// A code report containing the source code in UTF32 and the spans,
 /// text, and colors of all messages.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Report<'a, I> {
-    code: Utf32String,
-    messages: I,
-    realign: Option<&'a str>,
+///
+/// Message text can be anything that converts into a `Cow<str>`, so static
+/// labels can be passed as `&str` without allocating.
+///
+/// The messages are collected when the report is created, so a report can
+/// be displayed any number of times.
+///
+/// ```
+/// # use lyneate::Report;
+/// let report = Report::new_char_spanned("let value = 5;", [(4..9, "here", (255, 64, 112))]);
+///
+/// assert_eq!(
+///     report.render_plain(),
+///     "   1.  let value = 5; \n           ----- \n             | \n             \\-- here",
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Report<'a> {
+    // shared between clones, so that rendering other messages against the same
+    // code doesn't convert or copy it again
+    code: Arc<Utf32String>,
+    messages: Vec<StoredMessage<'a>>,
+    units: SpanUnits<'a>,
+    max_width: Option<usize>,
+    terminal_width: Option<usize>,
+    source_name: Option<Cow<'a, str>>,
+    // the url the line numbers link to, with `{n}` in place of the number
+    line_links: Option<Cow<'a, str>>,
+    // the number of the first line of the code, for code taken from further
+    // into a file
+    first_line: usize,
+    palette: Option<Vec<Color>>,
+    // indices of the messages colored from the palette, in the order they were added
+    auto_colored: Vec<usize>,
+    max_line_display_width: Option<usize>,
+    trim_trailing_whitespace: bool,
+    unicode_line_separators: bool,
+    ansi_source: bool,
+    indent: usize,
+    // lines shown even without messages on them
+    shown_lines: Range<usize>,
+    base_color: Option<Color>,
+    default_color: Option<Color>,
+    summary: bool,
+    max_messages: Option<usize>,
+    max_label_depth: Option<usize>,
+    // set for `render_plain`, leaves out all escape sequences
+    plain: bool,
     pub theme: Theme,
 }
 
-impl<'a, I> Report<'a, I>
+/// What the offsets of message spans count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpanUnits<'a> {
+    Chars,
+    /// Bytes of the code as given, to be realigned to chars.
+    Bytes(&'a str),
+    Utf16,
+}
+
+/// The part of the code a message points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MessageTarget {
+    /// A span in the units the report was created with.
+    Span(Range<usize>),
+    /// A whole line, by its 0-based index.
+    Line(usize),
+    /// A range of display columns on a line, both 0-based.
+    Columns(usize, Range<usize>),
+}
+
+/// A message as kept by a report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StoredMessage<'a> {
+    target: MessageTarget,
+    text: Cow<'a, str>,
+    color: Option<Color>,
+    priority: i32,
+    suggestion: Option<Cow<'a, str>>,
+    extra_spans: Vec<Range<usize>>,
+    underline: Option<char>,
+    underline_junction: Option<char>,
+    reversed: bool,
+    severity: Option<Severity>,
+}
+
+impl<'a> StoredMessage<'a> {
+    fn new(target: MessageTarget, text: Cow<'a, str>, color: Color) -> Self {
+        Self {
+            target,
+            text,
+            color: Some(color),
+            priority: 0,
+            suggestion: None,
+            extra_spans: vec![],
+            underline: None,
+            underline_junction: None,
+            reversed: false,
+            severity: None,
+        }
+    }
+}
+
+fn collect_messages<'a, I>(messages: I) -> Vec<StoredMessage<'a>>
 where
-    I: IntoIterator<Item = (Range<usize>, String, Color)>,
+    I: IntoIterator,
+    I::Item: Into<Message<'a>>,
 {
+    messages
+        .into_iter()
+        .map(|msg| {
+            let msg = msg.into();
+            StoredMessage {
+                target: MessageTarget::Span(msg.span),
+                text: msg.text,
+                color: msg.color,
+                priority: msg.priority,
+                suggestion: msg.suggestion,
+                extra_spans: msg.extra_spans,
+                underline: msg.underline,
+                underline_junction: msg.underline_junction,
+                reversed: msg.reversed,
+                severity: msg.severity,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+struct BoardCell {
+    color: Option<Color>,
+    ch: char,
+    // the whole grapheme cluster for source cells made up of several chars,
+    // or empty for the cells covered by a preceding wide cluster
+    cluster: Option<String>,
+    // source cells of a message drawn in reverse video
+    reversed: bool,
+}
+#[derive(Debug, Clone)]
+struct BoardRow {
+    line: Option<usize>,
+    cells: Vec<BoardCell>,
+    end_str: Option<String>,
+    // source rows with right-to-left text, whose cells are already in visual order
+    rtl: bool,
+    // source rows of lines no message touches, only shown around the others
+    context: bool,
+}
+impl BoardCell {
+    /// Returns what the cell shows, where tabs are shown as a space followed
+    /// by the cells they cover.
+    fn text(&self) -> String {
+        match (&self.cluster, self.ch) {
+            (Some(cluster), _) => cluster.clone(),
+            (None, '\t') => " ".into(),
+            (None, ch) => ch.into(),
+        }
+    }
+}
+impl BoardRow {
+    fn recolor(&mut self, span: MessageSpan, color: Option<Color>, reversed: bool) {
+        for i in span.start..span.end {
+            if let Some(c) = self.get_cell(i) {
+                c.color = color;
+                c.reversed = reversed;
+            }
+        }
+    }
+    fn mark_whitespace(&mut self, span: MessageSpan, space: char, tab: char) {
+        for i in span.start..span.end {
+            if let Some(c) = self.cells.get_mut(i).filter(|c| c.cluster.is_none()) {
+                match c.ch {
+                    ' ' => c.ch = space,
+                    '\t' => c.ch = tab,
+                    _ => (),
+                }
+            }
+        }
+    }
+    fn _write(&mut self, text: &str, start: usize) {
+        for (i, ch) in text.chars().enumerate() {
+            self.write_char(ch, i + start);
+        }
+    }
+    fn write_colored(&mut self, text: &str, start: usize, color: Option<Color>) {
+        for (i, ch) in text.chars().enumerate() {
+            self.write_colored_char(ch, i + start, color);
+        }
+    }
+    fn write_char(&mut self, ch: char, idx: usize) {
+        if let Some(c) = self.get_cell(idx) {
+            c.ch = ch;
+            c.cluster = None;
+        }
+    }
+    fn write_colored_char(&mut self, ch: char, idx: usize, color: Option<Color>) {
+        if let Some(c) = self.get_cell(idx) {
+            c.ch = ch;
+            c.cluster = None;
+            c.color = color;
+        }
+    }
+    fn set_end_str(&mut self, len: usize, end_str: Cow<str>) {
+        self.cells.resize(
+            len,
+            BoardCell {
+                color: None,
+                ch: ' ',
+                cluster: None,
+                reversed: false,
+            },
+        );
+        self.end_str = Some(end_str.into_owned());
+    }
+    fn get_cell(&mut self, idx: usize) -> Option<&mut BoardCell> {
+        if self.end_str.is_some() {
+            return self.cells.get_mut(idx);
+        }
+
+        if idx >= self.cells.len() {
+            self.cells.resize(
+                idx + 1,
+                BoardCell {
+                    color: None,
+                    ch: ' ',
+                    cluster: None,
+                    reversed: false,
+                },
+            );
+        }
+
+        self.cells.get_mut(idx)
+    }
+}
+
+/// The laid out report display, before it's turned into strings.
+struct Board {
+    rows: Vec<BoardRow>,
+    // the gutter text of every line shown, before the `line_numbers` effect
+    gutters: BTreeMap<usize, String>,
+    line_number_width: usize,
+    gutter_len: usize,
+    // the most severe message starting or ending on each line
+    severities: BTreeMap<usize, Severity>,
+}
+
+impl<'a> Report<'a> {
     /// Creates a new report from source code and messages with byte-aligned spans.
-    pub fn new_byte_spanned(code: &'a str, messages: I) -> Self {
+    ///
+    /// Span ends that fall inside a multibyte char are moved outwards to cover
+    /// the whole char.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// // `é` takes up bytes 1..3, so this span starts halfway through it
+    /// let report = Report::new_byte_spanned("héllo", [(2..4, "here", (255, 0, 0))]);
+    /// // and underlines `él`
+    /// assert_eq!(report.render_plain().lines().nth(1), Some("        -- "));
+    /// ```
+    ///
+    /// A byte order mark at the start of the code isn't shown, but still counts
+    /// towards the offsets of spans, so they can be taken from the file as is.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let report = Report::new_byte_spanned("\u{FEFF}let x;", [(3..6, "here", (255, 0, 0))])
+    ///     .with_source_name("bom.rs");
+    /// let plain = report.render_plain();
+    ///
+    /// assert!(plain.contains("bom.rs:1:1"));
+    /// assert_eq!(plain.lines().nth(1), Some("   1.  let x; "));
+    /// assert_eq!(plain.lines().nth(2), Some("       --- "));
+    /// ```
+    pub fn new_byte_spanned<I>(code: &'a str, messages: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Message<'a>>,
+    {
         let code_utf32 = Utf32String::from_str(code);
 
         Self {
-            code: code_utf32,
-            messages,
-            realign: Some(code),
+            code: Arc::new(code_utf32),
+            messages: collect_messages(messages),
+            units: SpanUnits::Bytes(code),
+            max_width: None,
+            terminal_width: None,
+            source_name: None,
+            line_links: None,
+            first_line: 1,
+            palette: None,
+            auto_colored: vec![],
+            max_line_display_width: None,
+            trim_trailing_whitespace: true,
+            unicode_line_separators: false,
+            ansi_source: false,
+            indent: 0,
+            shown_lines: 0..0,
+            base_color: None,
+            default_color: None,
+            summary: true,
+            max_messages: None,
+            max_label_depth: None,
+            plain: false,
             theme: Theme::default(),
         }
     }
     /// Creates a new report from source code and messages with char-aligned spans.
-    pub fn new_char_spanned(code: &str, messages: I) -> Self {
+    pub fn new_char_spanned<I>(code: &str, messages: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Message<'a>>,
+    {
         let code_utf32 = Utf32String::from_str(code);
 
         Self {
-            code: code_utf32,
-            messages,
-            realign: None,
+            code: Arc::new(code_utf32),
+            messages: collect_messages(messages),
+            units: SpanUnits::Chars,
+            max_width: None,
+            terminal_width: None,
+            source_name: None,
+            line_links: None,
+            first_line: 1,
+            palette: None,
+            auto_colored: vec![],
+            max_line_display_width: None,
+            trim_trailing_whitespace: true,
+            unicode_line_separators: false,
+            ansi_source: false,
+            indent: 0,
+            shown_lines: 0..0,
+            base_color: None,
+            default_color: None,
+            summary: true,
+            max_messages: None,
+            max_label_depth: None,
+            plain: false,
             theme: Theme::default(),
         }
     }
 
+    /// Creates a new report from source code and messages with spans in UTF-16
+    /// code units, as used by the positions of the language server protocol.
+    ///
+    /// Chars outside the basic multilingual plane take up two units, and span
+    /// ends that fall between them are moved outwards to cover the whole char.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// // `𝔸` and `😀` are two units each
+    /// let report = Report::new_utf16_spanned("𝔸 = 😀;", [(5..7, "here", (255, 0, 0))]);
+    /// assert_eq!(report.location(), Some((1, 5)));
+    ///
+    /// // ending halfway through `𝔸` still covers it
+    /// let report = Report::new_utf16_spanned("𝔸 = 😀;", [(0..1, "here", (255, 0, 0))]);
+    /// assert_eq!(report.render_plain().lines().nth(1), Some("       - "));
+    /// ```
+    pub fn new_utf16_spanned<I>(code: &str, messages: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Message<'a>>,
+    {
+        Self {
+            units: SpanUnits::Utf16,
+            ..Self::new_char_spanned(code, messages)
+        }
+    }
+    /// Creates a new report from code that is already split into lines,
+    /// without their line breaks, and messages with char-aligned spans.
+    ///
+    /// Spans are offsets into the lines as if they were joined with `\n`, so
+    /// every line starts one char after the end of the one before it.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let messages = [(4..5, "defined here", (64, 159, 255)), (19..20, "used here", (255, 64, 112))];
+    /// let report = Report::from_lines(&["let x = 5;", "let y = x;"], messages.clone());
+    ///
+    /// assert_eq!(
+    ///     report.render_plain(),
+    ///     Report::new_char_spanned("let x = 5;\nlet y = x;", messages).render_plain(),
+    /// );
+    /// ```
+    pub fn from_lines<I>(lines: &[&str], messages: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Message<'a>>,
+    {
+        let mut code = Utf32String::new();
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                code.push('\n');
+            }
+            code.push_str(line);
+        }
+
+        Self {
+            code: Arc::new(code),
+            ..Self::new_char_spanned("", messages)
+        }
+    }
+
+    /// Creates a report without any messages that shows the given lines of the
+    /// code, by their 0-based indices, for example as a preview of the code.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let report = Report::show_lines("fn main() {\n    run();\n}", 0..2).with_source_name("main.rs");
+    /// let plain = report.render_plain();
+    ///
+    /// assert_eq!(plain.lines().collect::<Vec<_>>(), [
+    ///     "   --> main.rs",
+    ///     "   1.  fn main() { ",
+    ///     "   2.      run(); ",
+    /// ]);
+    /// ```
+    pub fn show_lines(code: &str, lines: Range<usize>) -> Self {
+        Self::new_char_spanned(code, [] as [Message; 0]).with_lines_shown(lines)
+    }
+
     pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.set_theme(theme);
+        self
+    }
+    /// Replaces the theme of a report held by reference, like [`Report::with_theme`].
+    pub fn set_theme(&mut self, theme: Theme) {
         self.theme = theme;
+    }
+    /// Sets just the [`tab_width`](crate::ThemeSizing::tab_width) of the theme,
+    /// the number of columns between tab stops. Setting a whole theme afterwards
+    /// replaces it again.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let report = Report::new_char_spanned("\tx = 5;", [(1..2, "here", (255, 64, 112))]).with_tab_width(2);
+    ///
+    /// assert!(report.render_plain().starts_with("   1.    x = 5; "));
+    /// ```
+    pub fn with_tab_width(mut self, width: usize) -> Self {
+        self.theme.sizing.tab_width = width;
         self
     }
 
-    /// Returns the string of the pretty report display
-    pub fn display_str(self) -> String {
-        #[derive(Debug, Clone, Copy)]
-        struct LineInfo<'a> {
-            line: &'a Utf32Str,
-            start: usize,
-            end: usize,
-        }
-
-        let lines = {
-            let mut out = vec![];
-
-            let mut start = 0;
-
-            macro_rules! s {
-                ($s:expr) => {
-                    #[allow(unused_assignments)]
-                    {
-                        out.push(LineInfo {
-                            line: $s,
-                            start,
-                            end: start + $s.len(),
-                        });
-                        start += $s.len();
-                    }
+    /// Replaces the source code while keeping the messages and settings, for
+    /// example to show the same messages against two versions of a file.
+    ///
+    /// Spans are reinterpreted against the new code, in the units the report
+    /// was created with.
+    pub fn with_code(mut self, code: &'a str) -> Self {
+        self.code = Arc::new(Utf32String::from_str(code));
+        if let SpanUnits::Bytes(_) = self.units {
+            self.units = SpanUnits::Bytes(code);
+        }
+        self
+    }
+
+    /// Numbers the lines of the code from `number` instead of 1, in the gutter,
+    /// the header and the line links.
+    #[cfg(feature = "annotate-snippets")]
+    pub(crate) fn with_first_line(mut self, number: usize) -> Self {
+        self.first_line = number;
+        self
+    }
+
+    /// Shows a header above the code with the name of the source (usually its
+    /// file path), followed by the line and column of the earliest message.
+    pub fn with_source_name(mut self, name: impl Into<Cow<'a, str>>) -> Self {
+        self.source_name = Some(name.into());
+        self
+    }
+    /// Makes the line numbers in the gutter links that terminals supporting
+    /// OSC 8 hyperlinks can open, to the url `template` with `{n}` replaced by
+    /// the 1-based line number.
+    ///
+    /// The links are left out wherever colors are, like in
+    /// [`Report::render_plain`], or with the `std` feature when `NO_COLOR` is set
+    /// or the output is not a terminal.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// colored::control::set_override(true);
+    /// let report = Report::new_char_spanned("let a = 1;\nlet b = a;", [(19..20, "here", (255, 64, 112))])
+    ///     .with_line_links("file:///src/main.rs#L{n}");
+    ///
+    /// assert!(report.display_str().contains("\x1b]8;;file:///src/main.rs#L2\x1b\\2.\x1b]8;;\x1b\\"));
+    /// assert!(lyneate::strip_ansi(&report.display_str()).starts_with("   2.  let b = a;"));
+    /// assert!(!report.render_plain().contains('\x1b'));
+    /// ```
+    pub fn with_line_links(mut self, template: impl Into<Cow<'a, str>>) -> Self {
+        self.line_links = Some(template.into());
+        self
+    }
+
+    /// Adds more messages after the ones the report was created with, as if
+    /// they were passed to the constructor after them.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let base = Report::new_char_spanned("fn f() { g() }", [(0..14, "in this function", (64, 159, 255))]);
+    /// let report = base.with_messages([(9..12, "error here", (255, 64, 112))]);
+    ///
+    /// assert!(report.render_plain().contains("error here"));
+    /// ```
+    pub fn with_messages<I>(mut self, messages: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Message<'a>>,
+    {
+        self.messages.extend(collect_messages(messages));
+        self
+    }
+
+    /// Adds the messages of another report over the same code after the ones
+    /// of this report, so both are drawn in one display. The settings and theme
+    /// of this report are kept.
+    ///
+    /// The reports may count their spans in different units. Messages the
+    /// other report colored from its palette get the next colors of this one's
+    /// (see [`Report::add_message_auto`]).
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let code = "fn f() {\n    g()\n}";
+    /// let calls = Report::new_char_spanned(code, [(13..16, "call", (255, 64, 112))]);
+    /// let bodies = Report::new_byte_spanned(code, [(7..18, "body", (64, 159, 255))]);
+    /// let plain = calls.merge(bodies).render_plain();
+    ///
+    /// assert_eq!(plain.lines().collect::<Vec<_>>(), [
+    ///     "   1.  /-> fn f() { ",
+    ///     "   2.  |       g() ",
+    ///     "       |       --- ",
+    ///     "       |        | ",
+    ///     "       |        \\-- call",
+    ///     "   3.  |-> } ",
+    ///     "       | ",
+    ///     "       \\-- body",
+    /// ]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reports are not over the same code.
+    pub fn merge(mut self, mut other: Report<'a>) -> Self {
+        assert!(
+            self.code == other.code,
+            "merged reports must be over the same code"
+        );
+        if self.units != other.units {
+            self.materialize_char_spans();
+            other.materialize_char_spans();
+        }
+
+        let offset = self.messages.len();
+        self.messages.extend(other.messages);
+        for msg in other.auto_colored {
+            self.messages[offset + msg].color = Some(self.palette_color(self.auto_colored.len()));
+            self.auto_colored.push(offset + msg);
+        }
+        self
+    }
+    /// Resolves the spans of all messages to chars, so that the report counts
+    /// its spans in chars from then on.
+    fn materialize_char_spans(&mut self) {
+        let lines = Lines::new(&self.code, self.unicode_line_separators);
+        let to_chars = |span: &Range<usize>| {
+            let span = self.char_span(&MessageTarget::Span(span.clone()), &lines);
+            span.start..span.end
+        };
+        let messages = self
+            .messages
+            .iter()
+            .map(|msg| StoredMessage {
+                target: match &msg.target {
+                    MessageTarget::Span(span) => MessageTarget::Span(to_chars(span)),
+                    target => target.clone(),
+                },
+                extra_spans: msg.extra_spans.iter().map(to_chars).collect(),
+                ..msg.clone()
+            })
+            .collect();
+        self.messages = messages;
+        self.units = SpanUnits::Chars;
+    }
+
+    /// Adds a message underlining a whole line, given by its 0-based index,
+    /// without trailing whitespace. Lines past the end of the code refer to
+    /// the last line.
+    pub fn add_line_message(&mut self, line: usize, msg: impl Into<Cow<'a, str>>, color: Color) {
+        self.messages.push(StoredMessage::new(
+            MessageTarget::Line(line),
+            msg.into(),
+            color,
+        ));
+    }
+
+    /// Adds a message pointing at a range of display columns on a line, both
+    /// 0-based, instead of at offsets into the code. Every char at least partly
+    /// inside the range is covered, and lines past the end of the code refer to
+    /// the last line.
+    ///
+    /// ```
+    /// # use lyneate::{Message, Report};
+    /// let mut report = Report::new_char_spanned("let a = 1;\nlet 名前 = 2;", [] as [Message; 0]);
+    /// // `名前` takes up 4 columns, so `=` is at column 9
+    /// report.add_column_message(1, 9..10, "here", (255, 64, 112));
+    ///
+    /// assert!(report.render_plain().contains("                -"));
+    /// assert_eq!(report.location(), Some((2, 10)));
+    /// ```
+    pub fn add_column_message(
+        &mut self,
+        line: usize,
+        columns: Range<usize>,
+        msg: impl Into<Cow<'a, str>>,
+        color: Color,
+    ) {
+        self.messages.push(StoredMessage::new(
+            MessageTarget::Columns(line, columns),
+            msg.into(),
+            color,
+        ));
+    }
+    /// Adds a message pointing at the single position `offset`, in the units
+    /// the report was created with, rather than at a span, for example at the
+    /// gap where something is missing. The position gets a single junction (or
+    /// caret, with [`UnderlineStyle::Caret`](crate::UnderlineStyle::Caret))
+    /// with the arm attached, without an underline.
+    ///
+    /// ```
+    /// # use lyneate::{Message, Report};
+    /// let mut report = Report::new_char_spanned("let x = foo bar;", [] as [Message; 0]);
+    /// report.add_point(11, "expected `,`", (255, 64, 112));
+    ///
+    /// assert_eq!(report.render_plain().lines().collect::<Vec<_>>(), [
+    ///     "   1.  let x = foo bar; ",
+    ///     "                  - ",
+    ///     "                  | ",
+    ///     "                  \\-- expected `,`",
+    /// ]);
+    /// ```
+    pub fn add_point(&mut self, offset: usize, msg: impl Into<Cow<'a, str>>, color: Color) {
+        self.messages.push(StoredMessage::new(
+            MessageTarget::Span(offset..offset),
+            msg.into(),
+            color,
+        ));
+    }
+
+    /// Adds a message with a span in the units the report was created with,
+    /// colored with the next color of the palette.
+    ///
+    /// The colors are assigned in the order messages are added, so the output
+    /// is the same every time (see [`Report::with_palette`]).
+    pub fn add_message_auto(&mut self, span: Range<usize>, msg: impl Into<Cow<'a, str>>) {
+        let color = self.palette_color(self.auto_colored.len());
+        self.auto_colored.push(self.messages.len());
+        self.messages.push(StoredMessage::new(
+            MessageTarget::Span(span),
+            msg.into(),
+            color,
+        ));
+    }
+
+    /// Sets the colors cycled through by [`Report::add_message_auto`], including
+    /// for the messages added before.
+    ///
+    /// By default every message gets a new hue, spaced by the golden ratio from
+    /// the previous one. An empty palette restores the default.
+    pub fn with_palette(mut self, palette: Vec<Color>) -> Self {
+        self.palette = Some(palette).filter(|p| !p.is_empty());
+        for (i, &msg) in self.auto_colored.iter().enumerate() {
+            self.messages[msg].color = Some(self.palette_color(i));
+        }
+        self
+    }
+
+    fn palette_color(&self, idx: usize) -> Color {
+        palette_color(self.palette.as_deref(), idx)
+    }
+    /// Returns how many messages are colored from the palette.
+    pub(crate) fn auto_colored_len(&self) -> usize {
+        self.auto_colored.len()
+    }
+    /// Sets the color of the `idx`th message colored from the palette, for a
+    /// palette shared with other reports.
+    pub(crate) fn set_auto_color(&mut self, idx: usize, color: Color) {
+        let msg = self.auto_colored[idx];
+        self.messages[msg].color = Some(color);
+    }
+
+    /// Wraps message labels so that no row of the display is wider than `width`.
+    ///
+    /// If this is not set, the width of the terminal is used instead
+    /// (see [`Report::with_terminal_width`]).
+    pub fn with_max_width(mut self, width: usize) -> Self {
+        self.max_width = Some(width);
+        self
+    }
+    /// Shows only part of source lines wider than `width` columns, around the
+    /// spans on them, with `…` (or `...` in [plain](Report::render_plain)
+    /// output) where the line is cut off.
+    ///
+    /// Spans outside of the shown part point at the marker on their side
+    /// instead. At least one column of a line is always shown, so a `width`
+    /// too small for the markers on both sides still shows the markers and one
+    /// column between them.
+    pub fn with_max_line_display_width(mut self, width: usize) -> Self {
+        self.max_line_display_width = Some(width);
+        self
+    }
+    /// Whether spaces and tabs at the end of lines are left out of the display
+    /// and of spans pointing at whole lines. This is on by default.
+    ///
+    /// Other whitespace, like a trailing non-breaking space, is never trimmed.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let report = Report::new_char_spanned("a\u{A0}", [(0..1, "here", (255, 64, 112))]);
+    /// assert!(report.render_plain().contains("a\u{A0}"));
+    ///
+    /// let report = Report::new_char_spanned("a  ", [(0..1, "here", (255, 64, 112))])
+    ///     .with_trim_trailing_whitespace(false);
+    /// assert!(report.render_plain().contains("a  "));
+    /// ```
+    pub fn with_trim_trailing_whitespace(mut self, trim: bool) -> Self {
+        self.trim_trailing_whitespace = trim;
+        self
+    }
+    /// Sets whether the Unicode line and paragraph separators (U+2028 and
+    /// U+2029) end lines like `\n` does, as they do in JavaScript.
+    ///
+    /// Spans are still offsets into the whole code, but turning this on changes
+    /// the line and column they are at, both in the display and for
+    /// [`Report::location`] and the line and column based messages.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let code = "let a = 1;\u{2028}let b = 2;";
+    /// let report = Report::new_char_spanned(code, [(15..16, "here", (255, 64, 112))])
+    ///     .with_unicode_line_separators(true);
+    /// let plain = report.render_plain();
+    ///
+    /// assert_eq!(report.location(), Some((2, 5)));
+    /// assert!(plain.contains("   2.  let b = 2;"));
+    /// assert!(!plain.contains('\u{2028}'));
+    /// ```
+    pub fn with_unicode_line_separators(mut self, separators: bool) -> Self {
+        self.unicode_line_separators = separators;
+        self
+    }
+    /// Sets whether ANSI escape sequences in the code, like the colors of a
+    /// captured terminal session, are kept as they are in the display rather
+    /// than shown as text, without taking up any columns, so underlines line up
+    /// with what is visible.
+    ///
+    /// Only control sequences (`ESC [` up to a final byte, as in SGR codes like
+    /// `\x1b[31m`), operating system commands (`ESC ]` up to BEL or `ESC \`, as
+    /// in OSC 8 links) and two-char escapes are recognized. Spans still count the
+    /// chars of the sequences, which belong to the visible char after them.
+    /// [`Report::render_plain`] leaves the sequences out.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let code = "status: \x1b[31mfailed\x1b[0m (3 tests)";
+    /// let report = Report::new_char_spanned(code, [(13..19, "here", (255, 64, 112))])
+    ///     .with_ansi_source(true);
+    ///
+    /// assert!(report.display_str().contains("\x1b[31m"));
+    /// assert_eq!(report.render_plain().lines().collect::<Vec<_>>(), [
+    ///     "   1.  status: failed (3 tests) ",
+    ///     "               ------ ",
+    ///     "                  | ",
+    ///     "                  \\-- here",
+    /// ]);
+    /// ```
+    pub fn with_ansi_source(mut self, ansi: bool) -> Self {
+        self.ansi_source = ansi;
+        self
+    }
+    /// Shows the given lines, by their 0-based indices, whether or not any
+    /// messages point at them. Lines past the end of the code are left out.
+    pub fn with_lines_shown(mut self, lines: Range<usize>) -> Self {
+        self.shown_lines = lines;
+        self
+    }
+    /// Colors all the parts of the display that no message colors, like the
+    /// code outside of spans, instead of applying the
+    /// [`unhighlighted`](crate::ThemeEffects::unhighlighted) effect to them.
+    pub fn with_base_color(mut self, color: Color) -> Self {
+        self.base_color = Some(color);
+        self
+    }
+    /// Sets whether a summary of how many messages there are of each
+    /// [severity](Message::severity), like `2 errors, 1 warning`, is shown below
+    /// the report, each count in the color of its severity. It's shown by
+    /// default, but only if any message has a severity.
+    ///
+    /// ```
+    /// # use lyneate::{Message, Report, Severity};
+    /// let report = Report::new_char_spanned(
+    ///     "let value = 5;",
+    ///     [
+    ///         Message::new(0..3, "here", Severity::Error.color()).with_severity(Severity::Error),
+    ///         Message::new(4..9, "and here", Severity::Error.color()).with_severity(Severity::Error),
+    ///         Message::new(12..13, "unused", Severity::Warning.color())
+    ///             .with_severity(Severity::Warning),
+    ///     ],
+    /// );
+    ///
+    /// assert!(report.render_plain().ends_with("\n   2 errors, 1 warning"));
+    /// assert!(!report.with_summary(false).render_plain().contains("errors"));
+    /// ```
+    pub fn with_summary(mut self, summary: bool) -> Self {
+        self.summary = summary;
+        self
+    }
+    /// Only draws the `max` messages with the highest
+    /// [priority](Message::priority), or the first ones given among those of
+    /// equal priority, with a row saying how many were left out below the
+    /// report. The [summary](Report::with_summary) still counts all of them.
+    ///
+    /// ```
+    /// # use lyneate::{Message, Report};
+    /// let code = "a b c d e f g h i j";
+    /// let report = Report::new_char_spanned(
+    ///     code,
+    ///     (0..10).map(|i| Message::new(i * 2..i * 2 + 1, format!("msg {i}"), (255, 64, 112))),
+    /// )
+    /// .with_max_messages(3);
+    /// let plain = report.render_plain();
+    ///
+    /// assert_eq!(plain.lines().nth(2).unwrap().matches('|').count(), 3);
+    /// assert!(plain.contains("msg 2") && !plain.contains("msg 3"));
+    /// assert!(plain.ends_with("... and 7 more"));
+    /// ```
+    pub fn with_max_messages(mut self, max: usize) -> Self {
+        self.max_messages = Some(max);
+        self
+    }
+    /// Only labels the first `depth` single-line messages below (or above) each
+    /// line, in the [label order](crate::ThemeOptions::label_order), with a row
+    /// saying how many more there are on the line after them. The messages left
+    /// over are still underlined.
+    ///
+    /// ```
+    /// # use lyneate::{Message, Report};
+    /// let report = Report::new_char_spanned(
+    ///     "a b c d e f",
+    ///     (0..6).map(|i| Message::new(i * 2..i * 2 + 1, format!("msg {i}"), (255, 64, 112))),
+    /// )
+    /// .with_max_label_depth(3);
+    ///
+    /// assert_eq!(report.render_plain().lines().collect::<Vec<_>>(), [
+    ///     "   1.  a b c d e f ",
+    ///     "       - - - - - - ",
+    ///     "       | | | ",
+    ///     "       \\-|-|- msg 0",
+    ///     "         | | ",
+    ///     "         \\-|- msg 1",
+    ///     "           | ",
+    ///     "           \\-- msg 2",
+    ///     "        +3 more on this line",
+    /// ]);
+    /// ```
+    pub fn with_max_label_depth(mut self, depth: usize) -> Self {
+        self.max_label_depth = Some(depth);
+        self
+    }
+    /// Returns the messages that are drawn, in the order they were given in.
+    fn shown_messages(&self) -> Vec<&StoredMessage<'a>> {
+        let Some(max) = self.max_messages else {
+            return self.messages.iter().collect();
+        };
+        // the sort is stable, so earlier messages win among equal priorities
+        let mut shown = (0..self.messages.len()).collect::<Vec<_>>();
+        shown.sort_by_key(|&i| core::cmp::Reverse(self.messages[i].priority));
+        shown.truncate(max);
+        shown.sort();
+        shown.into_iter().map(|i| &self.messages[i]).collect()
+    }
+    /// Returns how many messages there are of each [severity](Message::severity),
+    /// from the most to the least severe, leaving out the severities without
+    /// any messages.
+    ///
+    /// ```
+    /// # use lyneate::{Message, Report, Severity};
+    /// let report = Report::new_char_spanned(
+    ///     "let value = 5;",
+    ///     [
+    ///         Message::new(0..3, "a", (255, 64, 112)).with_severity(Severity::Note),
+    ///         Message::new(4..9, "b", (255, 64, 112)).with_severity(Severity::Error),
+    ///         Message::new(12..13, "c", (255, 64, 112)),
+    ///         Message::new(13..14, "d", (255, 64, 112)).with_severity(Severity::Note),
+    ///     ],
+    /// );
+    ///
+    /// assert_eq!(
+    ///     report.severity_counts(),
+    ///     [(Severity::Error, 1), (Severity::Note, 2)]
+    /// );
+    /// ```
+    pub fn severity_counts(&self) -> Vec<(Severity, usize)> {
+        [Severity::Error, Severity::Warning, Severity::Note]
+            .into_iter()
+            .map(|severity| {
+                let count = self
+                    .messages
+                    .iter()
+                    .filter(|m| m.severity == Some(severity))
+                    .count();
+                (severity, count)
+            })
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+    /// Sets the color of the messages given without one (see
+    /// [`Message::uncolored`]) that don't have a [severity](Message::severity)
+    /// to take the color of either. Without a default color they are white.
+    ///
+    /// ```
+    /// # use lyneate::{Message, Report};
+    /// let report = Report::new_char_spanned(
+    ///     "let value = 5;",
+    ///     [(0..3, "keyword"), (4..9, "name")],
+    /// )
+    /// .with_default_color((64, 159, 255))
+    /// .with_messages([Message::new(12..13, "number", (255, 64, 112))]);
+    /// let code = &report.layout()[0];
+    ///
+    /// assert_eq!(code.cells[0].color, Some((64, 159, 255)));
+    /// assert_eq!(code.cells[4].color, Some((64, 159, 255)));
+    /// assert_eq!(code.cells[12].color, Some((255, 64, 112)));
+    /// ```
+    pub fn with_default_color(mut self, color: Color) -> Self {
+        self.default_color = Some(color);
+        self
+    }
+    /// Returns the color of a message, falling back to the color of its
+    /// severity and then the default color of the report.
+    fn message_color(&self, msg: &StoredMessage) -> Color {
+        msg.color
+            .or(msg.severity.map(Severity::color))
+            .or(self.default_color)
+            .unwrap_or(FALLBACK_COLOR)
+    }
+    /// Prepends `spaces` spaces to every row of the display, on top of the
+    /// [padding](crate::ThemeSizing::pre_line_number_padding) of the theme, for
+    /// example to nest the report in a list. Labels are wrapped as if the rows
+    /// started after the indent.
+    pub fn with_indent(mut self, spaces: usize) -> Self {
+        self.indent = spaces;
+        self
+    }
+    /// Sets the terminal width used for wrapping when no max width is set,
+    /// instead of querying the actual terminal.
+    ///
+    /// With the `terminal_size` feature enabled the width is otherwise detected
+    /// automatically, and labels aren't wrapped when stdout is not a terminal.
+    pub fn with_terminal_width(mut self, width: usize) -> Self {
+        self.terminal_width = Some(width);
+        self
+    }
+
+    /// Resolves what a message points at to a span of chars in the code.
+    fn char_span(&self, target: &MessageTarget, lines: &Lines) -> MessageSpan {
+        match target {
+            MessageTarget::Span(span) => {
+                let span = normalized(span);
+                let span = match self.units {
+                    SpanUnits::Chars => span,
+                    SpanUnits::Bytes(code) => byte_span_to_char_span(code, span),
+                    SpanUnits::Utf16 => utf16_span_to_char_span(&self.code, span),
                 };
+                // spans can't point into a leading byte order mark, which isn't shown
+                MessageSpan {
+                    start: span.start.max(lines.start(0)),
+                    end: span.end.max(lines.start(0)),
+                }
             }
-
-            for (i, c) in self.code.as_char_slice().iter().enumerate() {
-                if *c == '\n' {
-                    s!(&self.code[start..(i + 1)]);
+            MessageTarget::Line(line) => {
+                let line = (*line).min(lines.len() - 1);
+                MessageSpan {
+                    start: lines.start(line),
+                    end: lines.start(line) + self.trimmed_len(lines, line),
                 }
             }
-            s!(&self.code[start..self.code.len()]);
+            MessageTarget::Columns(line, cols) => {
+                let line = (*line).min(lines.len() - 1);
+                let cols = normalized(cols);
+                LineColumns::new(
+                    lines.line(line),
+                    self.theme.sizing.tab_width,
+                    self.ansi_source,
+                )
+                .char_span(MessageSpan {
+                    start: cols.start,
+                    end: cols.end,
+                })
+                .plus(lines.start(line))
+            }
+        }
+    }
 
-            out
+    /// Returns the text in the gutter of a line, depending on the
+    /// [gutter mode](crate::ThemeOptions::gutter_mode).
+    fn gutter_text(&self, lines: &Lines, line: usize) -> String {
+        let number = || (self.theme.effects.line_number_format)(line + self.first_line);
+        let format_offset = |offset: usize| match self.theme.options.decimal_offsets {
+            true => offset.to_string(),
+            false => format!("{:#x}", offset),
         };
+        let byte_offset = || {
+            let bytes = self.code.as_char_slice()[..lines.start(line)]
+                .iter()
+                .map(|c| c.len_utf8())
+                .sum::<usize>();
+            format_offset(bytes)
+        };
+        match self.theme.options.gutter_mode {
+            GutterMode::LineNumber => number(),
+            GutterMode::ByteOffset => byte_offset(),
+            GutterMode::CharOffset => format_offset(lines.start(line)),
+            GutterMode::Both => format!("{} {}", number(), byte_offset()),
+        }
+    }
+
+    /// Returns the length of a line without its line break and, unless turned
+    /// off, trailing spaces and tabs.
+    fn trimmed_len(&self, lines: &Lines, line: usize) -> usize {
+        lines
+            .line(line)
+            .as_char_slice()
+            .iter()
+            .rposition(|c| match c {
+                ' ' | '\t' => !self.trim_trailing_whitespace,
+                c => !lines.is_break(*c),
+            })
+            .map_or(0, |i| i + 1)
+    }
 
-        let get_line = |c: usize| {
-            lines
+    /// Resolves every span of a message to chars in the code.
+    fn char_spans<'m>(
+        &'m self,
+        msg: &'m StoredMessage,
+        lines: &'m Lines,
+    ) -> impl Iterator<Item = MessageSpan> + 'm {
+        core::iter::once(self.char_span(&msg.target, lines)).chain(
+            msg.extra_spans
                 .iter()
-                .position(|line| line.start <= c && c < line.end)
-                .unwrap_or(lines.len() - 1)
+                .map(|span| self.char_span(&MessageTarget::Span(span.clone()), lines)),
+        )
+    }
+
+    /// Returns the 1-based line and column of the start of the earliest message,
+    /// or `None` if there are no messages.
+    ///
+    /// The column counts display columns, so wide characters count twice.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let report = Report::new_byte_spanned("fn main() {\n    oops();\n}", [(16..20, "here", (255, 0, 0))]);
+    /// assert_eq!(report.location(), Some((2, 5)));
+    /// ```
+    pub fn location(&self) -> Option<(usize, usize)> {
+        let lines = Lines::new(&self.code, self.unicode_line_separators);
+
+        self.shown_messages()
+            .into_iter()
+            .map(|msg| self.message_location(msg, &lines))
+            .min()
+    }
+    /// Returns the 1-based line and column of the start of a message.
+    fn message_location(&self, msg: &StoredMessage, lines: &Lines) -> (usize, usize) {
+        let start = self
+            .char_spans(msg, lines)
+            .map(|span| span.start)
+            .min()
+            .unwrap();
+        let line = lines.line_of(start);
+        let column = LineColumns::new(
+            lines.line(line),
+            self.theme.sizing.tab_width,
+            self.ansi_source,
+        )
+        .col(start - lines.start(line));
+
+        (line + 1, column + 1)
+    }
+
+    /// Returns the messages on a single line, each as its
+    /// [location](Report::location) followed by its text, without any code,
+    /// for logs and other places too cramped for the full display.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let report = Report::new_char_spanned(
+    ///     "fn main() {\n    oops();\n}",
+    ///     [(16..20, "unknown function", (255, 0, 0)), (0..2, "in here", (64, 159, 255))],
+    /// );
+    ///
+    /// assert_eq!(
+    ///     report.render_inline(),
+    ///     "line 2, col 5: unknown function; line 1, col 1: in here"
+    /// );
+    /// ```
+    pub fn render_inline(&self) -> String {
+        let lines = Lines::new(&self.code, self.unicode_line_separators);
+
+        self.shown_messages()
+            .into_iter()
+            .map(|msg| {
+                let (line, col) = self.message_location(msg, &lines);
+                format!("line {}, col {}: {}", line, col, msg.text)
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// Returns the string of the pretty report display
+    pub fn display_str(&self) -> String {
+        self.rows().collect::<Vec<_>>().join("\n")
+    }
+    /// Returns the string of the pretty report display with `messages` in place
+    /// of the messages of the report, which are left as they are.
+    ///
+    /// This suits a report prepared once with the code and settings, usually
+    /// without messages, that is rendered with different sets of messages.
+    /// Unlike creating a report for each with `Report::new_*_spanned`, the code
+    /// is only converted once and is shared, not copied, between the displays.
+    ///
+    /// ```
+    /// # use lyneate::{Message, Report};
+    /// let report = Report::new_char_spanned("let value = 5;", [] as [Message; 0]);
+    ///
+    /// for (span, text) in [(0..3, "keyword"), (4..9, "name")] {
+    ///     let display = report.display_str_with([(span, text, (255, 64, 112))]);
+    ///     assert!(lyneate::strip_ansi(&display).contains(text));
+    /// }
+    /// ```
+    pub fn display_str_with<I>(&self, messages: I) -> String
+    where
+        I: IntoIterator,
+        I::Item: Into<Message<'a>>,
+    {
+        self.replacing_messages(messages).display_str()
+    }
+    /// Returns a copy of the report with only `messages`, sharing the code.
+    fn replacing_messages<I>(&self, messages: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Message<'a>>,
+    {
+        Self {
+            messages: collect_messages(messages),
+            auto_colored: vec![],
+            ..self.clone()
+        }
+    }
+
+    /// Returns the report display using [`ThemeChars::ascii`](crate::ThemeChars::ascii)
+    /// and [`ThemeEffects::none`](crate::ThemeEffects::none), without any
+    /// colors or other escape sequences, for output that ends up somewhere
+    /// other than a terminal. The rest of the theme is kept.
+    ///
+    /// Only the code and labels themselves can still contain non-ASCII text.
+    /// Lines with right-to-left text are still written in visual order, but
+    /// without the left-to-right override around them, so the output reads as
+    /// laid out wherever the text isn't reordered again.
+    pub fn render_plain(&self) -> String {
+        let mut report = self.clone();
+        report.theme.chars = ThemeChars {
+            gutter_separator: self.theme.chars.gutter_separator.map(|_| '|'),
+            underline_pattern: self.theme.chars.underline_pattern.filter(|p| p.is_ascii()),
+            ..ThemeChars::ascii()
         };
+        report.theme.effects = ThemeEffects::none();
+        report.plain = true;
+        report.display_str()
+    }
 
-        #[derive(Debug, Clone)]
-        struct LinearMsg {
+    /// Lays out the whole report display.
+    fn board(&self) -> Board {
+        let lines = Lines::new(&self.code, self.unicode_line_separators);
+
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        struct LinearMsg<'a> {
             color: Color,
             span: MessageSpan,
-            msg: String,
+            // more spans on the line sharing the arm
+            extra: Vec<MessageSpan>,
+            msg: Cow<'a, str>,
+            // whether the label is shown here, rather than on a later line
+            labeled: bool,
+            priority: i32,
+            suggestion: Option<Cow<'a, str>>,
+            // overrides of the theme's underline chars
+            underline: Option<char>,
+            underline_junction: Option<char>,
+            reversed: bool,
+            // the index of the message, for `LabelOrder::Input`
+            order: usize,
+        }
+        impl LinearMsg<'_> {
+            fn spans(&self) -> impl Iterator<Item = MessageSpan> + '_ {
+                core::iter::once(self.span).chain(self.extra.iter().copied())
+            }
         }
         #[derive(Debug, Clone)]
-        struct MultilineMsg {
+        struct MultilineMsg<'a> {
+            // the index of the message, shared by all its multiline spans
+            id: usize,
             color: Color,
+            reversed: bool,
 
             start_line: usize,
             end_line: usize,
@@ -113,77 +1252,225 @@ where
             pre_len: usize,
             end_len: usize,
 
-            msg: String,
+            msg: Cow<'a, str>,
         }
 
         let mut linear: BTreeMap<usize, Vec<LinearMsg>> = BTreeMap::new();
         let mut multiline: Vec<MultilineMsg> = vec![];
 
-        for (span, msg, color) in self.messages {
-            let span = MessageSpan {
-                start: span.start,
-                end: span.end,
-            };
-            let span = if let Some(code) = self.realign {
-                byte_span_to_char_span(code, span)
-            } else {
-                span
-            };
+        // display columns of every line that a message touches, from here on
+        // all spans are in columns rather than chars
+        let mut columns: BTreeMap<usize, LineColumns> = BTreeMap::new();
+
+        if let Some(max_line_width) = self.max_line_display_width {
+            // the spans on each line, to pick the part of lines that are too wide to show
+            let mut focus: BTreeMap<usize, Vec<MessageSpan>> = BTreeMap::new();
+            for span in self
+                .shown_messages()
+                .into_iter()
+                .flat_map(|msg| self.char_spans(msg, &lines))
+            {
+                let start_line = lines.line_of(span.start);
+                let (end_line, end) = lines.span_end(span.start, span.end);
+                let span = MessageSpan { end, ..span };
+
+                let start_columns = columns.entry(start_line).or_insert_with(|| {
+                    LineColumns::new(
+                        lines.line(start_line),
+                        self.theme.sizing.tab_width,
+                        self.ansi_source,
+                    )
+                });
+                let start = span.sub(lines.start(start_line));
+                if start_line == end_line {
+                    focus
+                        .entry(start_line)
+                        .or_default()
+                        .push(start_columns.span(start));
+                } else {
+                    let col = start_columns.col(start.start);
+                    focus.entry(start_line).or_default().push(MessageSpan {
+                        start: col,
+                        end: col + 1,
+                    });
+                    let col = columns
+                        .entry(end_line)
+                        .or_insert_with(|| {
+                            LineColumns::new(
+                                lines.line(end_line),
+                                self.theme.sizing.tab_width,
+                                self.ansi_source,
+                            )
+                        })
+                        .end_col(span.end - lines.start(end_line));
+                    focus.entry(end_line).or_default().push(MessageSpan {
+                        start: col.saturating_sub(1),
+                        end: col,
+                    });
+                }
+            }
+            let marker = if self.plain { PLAIN_ELLIPSIS } else { ELLIPSIS };
+            for (line, focus) in focus {
+                columns
+                    .get_mut(&line)
+                    .unwrap()
+                    .fit(max_line_width, &focus, marker);
+            }
+        }
 
-            let start_line = get_line(span.start);
-            let end_line = get_line(span.end);
+        for (id, stored) in self.shown_messages().into_iter().enumerate() {
+            let (msg, color) = (stored.text.clone(), self.message_color(stored));
+            // the single-line spans of the message by line
+            let mut line_spans: BTreeMap<usize, Vec<MessageSpan>> = BTreeMap::new();
+
+            for span in self.char_spans(stored, &lines) {
+                let start_line = lines.line_of(span.start);
+                let (end_line, end) = lines.span_end(span.start, span.end);
+                let span = MessageSpan { end, ..span };
+
+                let start_columns = columns.entry(start_line).or_insert_with(|| {
+                    LineColumns::new(
+                        lines.line(start_line),
+                        self.theme.sizing.tab_width,
+                        self.ansi_source,
+                    )
+                });
+
+                if start_line == end_line {
+                    line_spans
+                        .entry(start_line)
+                        .or_default()
+                        .push(start_columns.span(span.sub(lines.start(start_line))));
+                    continue;
+                }
+
+                let pre_len = start_columns.col(span.start - lines.start(start_line));
+                let end_len = columns
+                    .entry(end_line)
+                    .or_insert_with(|| {
+                        LineColumns::new(
+                            lines.line(end_line),
+                            self.theme.sizing.tab_width,
+                            self.ansi_source,
+                        )
+                    })
+                    .end_col(span.end - lines.start(end_line));
 
-            if start_line == end_line {
-                linear.entry(start_line).or_default().push(LinearMsg {
-                    color,
-                    span: span.sub(lines[start_line].start),
-                    msg,
-                })
-            } else {
                 multiline.push(MultilineMsg {
+                    id,
                     color,
+                    reversed: stored.reversed,
                     start_line,
                     end_line,
-                    pre_len: span.start - lines[start_line].start,
-                    end_len: span.end - lines[end_line].start,
-                    msg,
+                    pre_len,
+                    end_len,
+                    msg: msg.clone(),
                 })
             }
+
+            let label_line = line_spans.keys().last().copied();
+            for (line, mut spans) in line_spans {
+                spans.sort_by_key(|s| (s.start, s.end));
+                spans.dedup();
+                // messages without text are only underlined
+                let labeled = Some(line) == label_line && !msg.is_empty();
+
+                let msg = LinearMsg {
+                    color,
+                    span: spans[0],
+                    extra: spans[1..].to_vec(),
+                    msg: if labeled { msg.clone() } else { "".into() },
+                    labeled,
+                    priority: stored.priority,
+                    suggestion: stored.suggestion.clone().filter(|_| labeled),
+                    underline: stored.underline,
+                    underline_junction: stored.underline_junction,
+                    reversed: stored.reversed,
+                    order: id,
+                };
+                let line_msgs = linear.entry(line).or_default();
+                // exact duplicates would just draw the same underline and label twice
+                if !line_msgs.iter().any(|m| {
+                    *m == LinearMsg {
+                        order: m.order,
+                        ..msg.clone()
+                    }
+                }) {
+                    line_msgs.push(msg)
+                }
+            }
         }
 
         #[derive(Debug, Clone)]
-        struct MultilineGroup {
+        struct MultilineGroup<'a> {
             first_line: usize,
             last_line: usize,
-            msgs: Vec<MultilineMsg>,
+            msgs: Vec<MultilineMsg<'a>>,
         }
 
-        let mut multiline_groups: Vec<MultilineGroup> = vec![];
+        for line in self.shown_lines.start..self.shown_lines.end.min(lines.len()) {
+            columns.entry(line).or_insert_with(|| {
+                LineColumns::new(
+                    lines.line(line),
+                    self.theme.sizing.tab_width,
+                    self.ansi_source,
+                )
+            });
+            linear.entry(line).or_default();
+        }
 
-        'outer: for msg in multiline {
-            for group in &mut multiline_groups {
-                if group.first_line <= msg.end_line && msg.start_line <= group.last_line {
-                    if msg.start_line < group.first_line {
-                        group.first_line = msg.start_line;
-                    }
-                    if msg.end_line > group.last_line {
-                        group.last_line = msg.end_line;
-                    }
-                    group.msgs.push(msg);
-                    continue 'outer;
-                }
-            }
-            multiline_groups.push(MultilineGroup {
+        // grouped in a fixed order, so that the side columns don't depend on the
+        // order the messages were given in
+        multiline.sort_by(|a, b| {
+            (
+                a.start_line,
+                a.end_line,
+                a.pre_len,
+                a.end_len,
+                &a.msg,
+                a.color,
+            )
+                .cmp(&(
+                    b.start_line,
+                    b.end_line,
+                    b.pre_len,
+                    b.end_len,
+                    &b.msg,
+                    b.color,
+                ))
+        });
+        let mut multiline_groups: Vec<MultilineGroup> = vec![];
+        for msg in multiline {
+            let mut group = MultilineGroup {
                 first_line: msg.start_line,
                 last_line: msg.end_line,
                 msgs: vec![msg],
-            })
+            };
+            // groups sharing any line use the same side columns, as do the spans
+            // of one message, so keep merging until none overlap, as a merged group
+            // can reach ones it didn't before
+            while let Some(idx) = multiline_groups.iter().position(|g| {
+                (g.first_line <= group.last_line && group.first_line <= g.last_line)
+                    || g.msgs
+                        .iter()
+                        .any(|m| group.msgs.iter().any(|n| n.id == m.id))
+            }) {
+                let other = multiline_groups.remove(idx);
+                group.first_line = group.first_line.min(other.first_line);
+                group.last_line = group.last_line.max(other.last_line);
+                group.msgs.splice(0..0, other.msgs);
+            }
+            multiline_groups.push(group)
         }
 
         #[derive(Debug, Clone)]
         struct FinalLine<S> {
-            underline_highlights: Vec<(S, Color)>,
-            multiline_highlights: Vec<(S, Color)>,
+            // the spans of the code highlighted in a color, and whether in
+            // reverse video
+            underline_highlights: Vec<(S, Color, bool)>,
+            multiline_highlights: Vec<(S, Color, bool)>,
+            // rows above the line, for labels of arms pointing up
+            above: usize,
             spacing: usize,
         }
         impl<S> FinalLine<S> {
@@ -191,6 +1478,7 @@ where
                 Self {
                     underline_highlights: vec![],
                     multiline_highlights: vec![],
+                    above: 0,
                     spacing: 0,
                 }
             }
@@ -201,214 +1489,644 @@ where
             .collect::<BTreeMap<_, _>>();
 
         #[derive(Debug, Clone)]
-        struct UnderlineCommand {
+        struct UnderlineCommand<'a> {
             line: usize,
-            span: MessageSpan,
-            msg: String,
+            spans: Vec<MessageSpan>,
+            // the parts of each span that aren't covered by later messages
+            visible: Vec<Vec<MessageSpan>>,
+            msg: Vec<Cow<'a, str>>,
             color: Color,
             depth: usize,
-            connector_pos: usize,
+            // where the arm attaches to each span, empty if there's no label
+            connectors: Vec<usize>,
+            // the column of the tip of the arm, right before the label
+            arm_end: usize,
+            // the replacement text and its row counting from the underline
+            suggestion: Option<(usize, Cow<'a, str>)>,
+            underline: Option<char>,
+            underline_junction: Option<char>,
+            // whether the connectors go on past the arm to the label of a message
+            // on the same spans
+            stacked: bool,
         }
         #[derive(Debug, Clone)]
-        struct MultilineCommand {
-            start_line: usize,
-            end_line: usize,
+        struct MultilineCommand<'a> {
+            // the first and last line of each span, in order
+            spans: Vec<(usize, usize)>,
 
-            spacing_end: usize,
+            // the line the label is placed next to
+            label_line: usize,
 
-            msg: String,
+            msg: Vec<Cow<'a, str>>,
 
             color: Color,
 
             depth: usize,
-            side_height: usize,
+            horiz: usize,
         }
 
         let mut underline_commands: Vec<UnderlineCommand> = vec![];
+        // the rows saying how many labels were left out of a line, by their line,
+        // depth, column and count
+        let mut overflows = vec![];
         let mut multiline_commands: Vec<MultilineCommand> = vec![];
 
-        let side_space = multiline_groups
+        // the lines that get a row of their own
+        let shown = {
+            let mut shown = linear
+                .keys()
+                .copied()
+                .chain(
+                    multiline_groups
+                        .iter()
+                        .flat_map(|g| g.msgs.iter().flat_map(|m| [m.start_line, m.end_line])),
+                )
+                .collect::<Vec<_>>();
+            shown.sort_unstable();
+            shown.dedup();
+            shown
+        };
+
+        // the room left of the code for the side bars of multiline messages, only
+        // reserved in the blocks of adjacent lines that a group's bars pass through
+        let side_spaces = {
+            // the spans of a message share a bar
+            let group_space = |g: &MultilineGroup| {
+                let mut ids = g.msgs.iter().map(|m| m.id).collect::<Vec<_>>();
+                ids.sort_unstable();
+                ids.dedup();
+                ids.len() * (self.theme.sizing.side_pointer_length + 1) + 1
+            };
+
+            let mut side_spaces = BTreeMap::new();
+            let mut block: Vec<usize> = vec![];
+            for (i, &line) in shown.iter().enumerate() {
+                block.push(line);
+                let next = shown.get(i + 1).copied();
+                let continues = next.is_some_and(|next| {
+                    next == line + 1
+                        || multiline_groups
+                            .iter()
+                            .any(|g| g.first_line <= line && next <= g.last_line)
+                });
+                if !continues {
+                    let space = multiline_groups
+                        .iter()
+                        .filter(|g| block.contains(&g.first_line))
+                        .map(group_space)
+                        .max()
+                        .unwrap_or(0);
+                    side_spaces.extend(block.drain(..).map(|l| (l, space)));
+                }
+            }
+            side_spaces
+        };
+
+        let gutters = shown
             .iter()
-            .map(|g| g.msgs.len())
+            .map(|&line| (line, self.gutter_text(&lines, line)))
+            .collect::<BTreeMap<_, _>>();
+        let line_number_width = gutters
+            .values()
+            .map(|text| visible_len(text))
             .max()
-            .map(|v| v * (self.theme.sizing.side_pointer_length + 1) + 1)
             .unwrap_or(0);
 
-        for (line, msgs) in linear {
-            let mut visible_spans = msgs.iter().map(|l| vec![l.span]).collect::<Vec<_>>();
+        let max_width = self
+            .max_width
+            .or(self.terminal_width)
+            .or_else(terminal_width);
+        // the vertical rule and its gap, if the report is framed
+        let frame_len = if self.theme.options.frame { 2 } else { 0 };
+        // the severity icon and its gap
+        let icon_len = if self.theme.options.severity_icons {
+            2
+        } else {
+            0
+        };
+        // the gutter separator and the space before it
+        let separator_len = if self.theme.chars.gutter_separator.is_some() {
+            2
+        } else {
+            0
+        };
+        // everything to the left of the board cells: the padding, frame, icon, line
+        // number, separator and gap
+        let gutter_len = self.theme.sizing.pre_line_number_padding
+            + frame_len
+            + icon_len
+            + line_number_width
+            + separator_len
+            + self.theme.sizing.gutter_code_gap;
+        let wrap_label = |msg: Cow<'a, str>, label_start: usize| match max_width {
+            Some(width) => wrap(
+                &msg,
+                width
+                    .saturating_sub(self.indent + gutter_len + label_start + 1)
+                    .max(1),
+            )
+            .into_iter()
+            .map(Cow::Owned)
+            .collect(),
+            None => vec![msg],
+        };
 
-            for i in 0..(visible_spans.len() - 1) {
-                for j in (i + 1)..visible_spans.len() {
-                    visible_spans[i] = visible_spans[i]
-                        .iter()
-                        .flat_map(|s| s.overlay(visible_spans[j][0]))
-                        .collect();
+        // wide spans are underlined without a junction, with the arm at their end
+        let is_flat = |span: MessageSpan| {
+            self.theme
+                .options
+                .flat_underline_width
+                .is_some_and(|width| span.size() >= width)
+        };
+
+        for (line, mut msgs) in linear {
+            msgs.sort_by(|a, b| {
+                let by_order = match self.theme.options.label_order {
+                    LabelOrder::Start => Ordering::Equal,
+                    LabelOrder::Length => b.span.size().cmp(&a.span.size()),
+                    LabelOrder::Input => a.order.cmp(&b.order),
+                };
+                a.priority.cmp(&b.priority).then(by_order).then_with(|| {
+                    (a.span.start, a.span.end, &a.msg, a.color).cmp(&(
+                        b.span.start,
+                        b.span.end,
+                        &b.msg,
+                        b.color,
+                    ))
+                })
+            });
+
+            // the labels past the deepest one allowed are left out, and only counted
+            let mut hidden = 0;
+            if let Some(max) = self.max_label_depth {
+                for msg in msgs.iter_mut().filter(|m| m.labeled).skip(max) {
+                    msg.labeled = false;
+                    msg.msg = "".into();
+                    msg.suggestion = None;
+                    hidden += 1;
                 }
             }
+            let overflow_col = msgs.iter().map(|m| m.span.start).min();
+
+            // what remains visible of each span of each message after the spans of
+            // the later messages are drawn over it
+            let visible_spans = msgs
+                .iter()
+                .enumerate()
+                .map(|(i, msg)| {
+                    msg.spans()
+                        .map(|span| {
+                            msgs[i + 1..].iter().flat_map(|m| m.spans()).fold(
+                                vec![span],
+                                |visible, over| {
+                                    visible.into_iter().flat_map(|s| s.overlay(over)).collect()
+                                },
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
 
-            for (msg, spans) in msgs.into_iter().zip(visible_spans) {
+            // a line with only unlabeled spans still needs a row for their underlines
+            if !msgs.is_empty() && !msgs.iter().any(|m| m.labeled) {
                 let fline = final_lines.get_mut(&line).unwrap();
+                match self.theme.options.arm_direction {
+                    ArmDirection::Down => fline.spacing += 1,
+                    ArmDirection::Up => fline.above += 1,
+                }
+            }
 
-                fline.underline_highlights.push((msg.span, msg.color));
-                fline.spacing +=
-                    if fline.spacing == 0 { 2 } else { 1 } + self.theme.sizing.underline_spacing;
-
-                let middle = msg.span.start + msg.span.size() / 2;
-                let connector_pos = 'outer: {
-                    let mut max_span = None;
-                    for span in spans {
-                        let diff = if (span.start..span.end).contains(&middle) {
-                            break 'outer span.start + span.size() / 2;
-                        } else if span.end <= middle {
-                            middle - span.end
-                        } else {
-                            span.start - middle - 1
-                        };
-                        if max_span.is_none() || max_span.is_some_and(|(_, v)| diff < v) {
-                            max_span = Some((span, diff))
-                        }
+            // a lone label can follow its underline on the same row, if it fits
+            // there and no other span on the line reaches past its start
+            let inline = {
+                let mut labeled = msgs.iter().filter(|m| m.labeled);
+                match (labeled.next(), labeled.next()) {
+                    (Some(msg), None) => {
+                        self.theme.options.inline_labels
+                            && msg.extra.is_empty()
+                            && msgs
+                                .iter()
+                                .all(|m| m.spans().all(|s| s.end <= msg.span.end))
+                            && wrap_label(msg.msg.clone(), msg.span.end + side_spaces[&line]).len()
+                                == 1
                     }
-                    max_span
-                        .map(|(s, _)| s.start + s.size() / 2)
-                        .unwrap_or(middle)
-                };
+                    _ => false,
+                }
+            };
+
+            // suggestions each get a row below the labels, where no connector or arm
+            // crosses them however long they are
+            let suggestion_rows = msgs.iter().filter(|m| m.suggestion.is_some()).count();
+            let mut suggestion_row = 0;
+            let line_commands = underline_commands.len();
+            // columns that already have a connector on this line
+            let mut taken_connectors = vec![];
+
+            let connector_anchor = self.theme.options.connector_anchor.unwrap_or(
+                match self.theme.options.underline_style {
+                    UnderlineStyle::Caret => ConnectorAnchor::Start,
+                    UnderlineStyle::Line => ConnectorAnchor::Middle,
+                },
+            );
+            let anchor = |span: MessageSpan, anchor| match anchor {
+                ConnectorAnchor::Start => span.start,
+                ConnectorAnchor::Middle => span.start + span.size() / 2,
+                ConnectorAnchor::End => span.end.saturating_sub(1).max(span.start),
+            };
+            // if the anchor is covered by other spans, attach to the visible
+            // part of the span that's closest to it instead
+            let connector = |span: MessageSpan, visible: &[MessageSpan]| {
+                let anchor = |s| match is_flat(span) {
+                    true => anchor(s, ConnectorAnchor::End),
+                    false => anchor(s, connector_anchor),
+                };
+                let target = anchor(span);
+                let mut max_span = None;
+                for &span in visible {
+                    let diff = if span.contains(target) {
+                        return anchor(span);
+                    } else if span.end <= target {
+                        target - span.end
+                    } else {
+                        span.start - target - 1
+                    };
+                    if max_span.is_none() || max_span.is_some_and(|(_, v)| diff < v) {
+                        max_span = Some((span, diff))
+                    }
+                }
+                max_span.map(|(s, _)| anchor(s)).unwrap_or(target)
+            };
+            // two connectors in the same column would draw one arm over the
+            // other, so a taken one moves to the closest free column of its span,
+            // preferring the visible parts, or else right next to the span
+            //
+            // messages on the exact same spans as an earlier one share its
+            // connectors instead, with their labels stacked along them
+            let same_spans =
+                |a: &LinearMsg, b: &LinearMsg| a.labeled && b.labeled && a.spans().eq(b.spans());
+            let mut connectors: Vec<Vec<usize>> = vec![];
+            for (i, (msg, visible)) in msgs.iter().zip(&visible_spans).enumerate() {
+                if !msg.labeled {
+                    connectors.push(vec![]);
+                    continue;
+                }
+                if let Some(shared) = msgs[..i].iter().position(|m| same_spans(m, msg)) {
+                    connectors.push(connectors[shared].clone());
+                    continue;
+                }
+                connectors.push(
+                    msg.spans()
+                        .zip(visible)
+                        .map(|(span, visible)| {
+                            let col = connector(span, visible);
+                            let closest_free = |cols: Vec<usize>| {
+                                cols.into_iter()
+                                    .filter(|c| !taken_connectors.contains(c))
+                                    .min_by_key(|c| c.abs_diff(col))
+                            };
+                            let col = if taken_connectors.contains(&col) {
+                                closest_free(visible.iter().flat_map(|s| s.start..s.end).collect())
+                                    .or_else(|| closest_free((span.start..span.end).collect()))
+                                    .or_else(|| {
+                                        closest_free(
+                                            [Some(span.end), span.start.checked_sub(1)]
+                                                .into_iter()
+                                                .flatten()
+                                                .collect(),
+                                        )
+                                    })
+                                    .unwrap_or(col)
+                            } else {
+                                col
+                            };
+                            taken_connectors.push(col);
+                            col
+                        })
+                        .collect::<Vec<_>>(),
+                );
+            }
+            // the messages with a later one stacked below them
+            let stacked = (0..msgs.len())
+                .map(|i| msgs[i + 1..].iter().any(|m| same_spans(m, &msgs[i])))
+                .collect::<Vec<_>>();
+
+            for (i, (msg, visible)) in msgs.into_iter().zip(visible_spans).enumerate() {
+                let fline = final_lines.get_mut(&line).unwrap();
+
+                fline
+                    .underline_highlights
+                    .extend(msg.spans().map(|span| (span, msg.color, msg.reversed)));
+                if !msg.labeled {
+                    underline_commands.push(UnderlineCommand {
+                        line,
+                        spans: msg.spans().collect(),
+                        visible,
+                        msg: vec![],
+                        color: msg.color,
+                        depth: 0,
+                        connectors: vec![],
+                        arm_end: 0,
+                        suggestion: None,
+                        underline: msg.underline,
+                        underline_junction: msg.underline_junction,
+                        stacked: false,
+                    });
+                    continue;
+                }
+                let spacing = match self.theme.options.arm_direction {
+                    ArmDirection::Down => &mut fline.spacing,
+                    ArmDirection::Up => &mut fline.above,
+                };
+                if inline {
+                    *spacing += 1;
+                    underline_commands.push(UnderlineCommand {
+                        line,
+                        spans: msg.spans().collect(),
+                        visible,
+                        msg: vec![msg.msg],
+                        color: msg.color,
+                        depth: 0,
+                        connectors: vec![],
+                        arm_end: 0,
+                        suggestion: msg.suggestion.map(|text| {
+                            suggestion_row += 1;
+                            (suggestion_row, text)
+                        }),
+                        underline: msg.underline,
+                        underline_junction: msg.underline_junction,
+                        stacked: false,
+                    });
+                    continue;
+                }
+                *spacing += if *spacing == 0 { 2 } else { 1 } + self.theme.sizing.underline_spacing;
+
+                let last_connector = *connectors[i].iter().max().unwrap();
+                // the connectors of the later messages cross the rows of this label,
+                // so its arm reaches past them to keep them from running into it
+                let arm_end = connectors[i + 1..].iter().flatten().map(|c| c + 1).fold(
+                    last_connector + self.theme.sizing.underline_arm_length,
+                    usize::max,
+                );
+                let spans = msg.spans().collect();
+
+                let msg_lines = wrap_label(msg.msg, arm_end + side_spaces[&line] + 1);
+                let depth = *spacing - 1;
+                *spacing += msg_lines.len() - 1;
+
+                let suggestion = msg.suggestion.map(|text| {
+                    suggestion_row += 1;
+                    (suggestion_row, text)
+                });
 
                 underline_commands.push(UnderlineCommand {
                     line,
-                    span: msg.span,
-                    msg: msg.msg,
+                    spans,
+                    visible,
+                    msg: msg_lines,
                     color: msg.color,
-                    depth: fline.spacing - 1,
-                    connector_pos,
+                    depth,
+                    connectors: connectors[i].clone(),
+                    arm_end,
+                    suggestion,
+                    underline: msg.underline,
+                    underline_junction: msg.underline_junction,
+                    stacked: stacked[i],
                 })
             }
+            if let Some(col) = overflow_col.filter(|_| hidden > 0) {
+                let fline = final_lines.get_mut(&line).unwrap();
+                let spacing = match self.theme.options.arm_direction {
+                    ArmDirection::Down => &mut fline.spacing,
+                    ArmDirection::Up => &mut fline.above,
+                };
+                *spacing += 1;
+                overflows.push((line, *spacing - 1, col, hidden));
+            }
+            if suggestion_rows > 0 {
+                let fline = final_lines.get_mut(&line).unwrap();
+                let spacing = match self.theme.options.arm_direction {
+                    ArmDirection::Down => &mut fline.spacing,
+                    ArmDirection::Up => &mut fline.above,
+                };
+                for command in &mut underline_commands[line_commands..] {
+                    if let Some((row, _)) = &mut command.suggestion {
+                        *row += *spacing - 1;
+                    }
+                }
+                *spacing += suggestion_rows;
+            }
         }
         for group in multiline_groups {
-            for (side, msg) in group.msgs.into_iter().enumerate() {
-                {
+            // the spans of each message, in order, which share a side bar and label
+            let mut bars: Vec<Vec<MultilineMsg>> = vec![];
+            for msg in group.msgs {
+                match bars.iter_mut().find(|bar| bar[0].id == msg.id) {
+                    Some(bar) => bar.push(msg),
+                    None => bars.push(vec![msg]),
+                }
+            }
+            for bar in &mut bars {
+                bar.sort_by_key(|msg| (msg.start_line, msg.end_line));
+            }
+            let extent = |bar: &[MultilineMsg]| {
+                (
+                    bar[0].start_line,
+                    bar.iter().map(|msg| msg.end_line).max().unwrap(),
+                )
+            };
+
+            // ranges that start later (and then end sooner) get the columns closest
+            // to the code and the labels closest to the group, so a range nested in
+            // another never has its arms cross the outer one's side bar. Partially
+            // overlapping ranges can't avoid this, there the earlier range's end
+            // arm points across the later range's bar.
+            //
+            // Labels at the start mirror this, there the ranges that end sooner are
+            // closest to the code.
+            match self.theme.options.multiline_label {
+                MultilineLabel::End => bars.sort_by_key(|bar| {
+                    let (start, end) = extent(bar);
+                    (core::cmp::Reverse(start), end)
+                }),
+                MultilineLabel::Start => bars.sort_by_key(|bar| {
+                    let (start, end) = extent(bar);
+                    (end, core::cmp::Reverse(start))
+                }),
+            }
+
+            for (side, bar) in bars.into_iter().enumerate() {
+                for msg in &bar {
                     let line = final_lines
                         .entry(msg.start_line)
                         .or_insert(FinalLine::new());
 
-                    line.multiline_highlights.push((
-                        MessageSpan {
-                            start: msg.pre_len,
-                            end: lines[msg.start_line].line.trim_end().len(),
-                        },
-                        msg.color,
-                    ));
-                }
-                let (spacing_end, depth) = {
-                    let hl_line = final_lines.entry(msg.end_line).or_insert(FinalLine::new());
+                    // inner ranges are drawn on top of the ones that contain them
+                    line.multiline_highlights.insert(
+                        0,
+                        (
+                            MessageSpan {
+                                start: msg.pre_len,
+                                end: columns[&msg.start_line]
+                                    .end_col(self.trimmed_len(&lines, msg.start_line)),
+                            },
+                            msg.color,
+                            msg.reversed,
+                        ),
+                    );
 
-                    hl_line.multiline_highlights.push((
-                        MessageSpan {
-                            start: 0,
-                            end: msg.end_len,
-                        },
-                        msg.color,
-                    ));
+                    let line = final_lines.entry(msg.end_line).or_insert(FinalLine::new());
+                    line.multiline_highlights.insert(
+                        0,
+                        (
+                            MessageSpan {
+                                start: 0,
+                                end: msg.end_len,
+                            },
+                            msg.color,
+                            msg.reversed,
+                        ),
+                    );
+                }
+                // every bar takes up its column and the pointer after it, with
+                // the first bar closest to the code. Saturating, so that a
+                // side space too small for the sizing squeezes the bars together
+                // instead of overflowing.
+                let horiz = side_spaces[&group.first_line]
+                    .saturating_sub((side + 1) * (self.theme.sizing.side_pointer_length + 1) + 1);
+                let msg_lines = wrap_label(
+                    bar[0].msg.clone(),
+                    horiz + self.theme.sizing.side_arm_length + 1,
+                );
 
-                    let spacing_end = msg.end_line.max(group.last_line);
+                let (label_line, depth) = match self.theme.options.multiline_label {
+                    MultilineLabel::End => {
+                        let spacing_end = extent(&bar).1.max(group.last_line);
+                        let spacing_line =
+                            final_lines.entry(spacing_end).or_insert(FinalLine::new());
+
+                        // the rows of the single-line messages on this line are all
+                        // taken by now, so the label goes below them
+                        spacing_line.spacing += 2;
+                        let depth = spacing_line.spacing;
+                        spacing_line.spacing += msg_lines.len() - 1;
+                        (spacing_end, depth)
+                    }
+                    MultilineLabel::Start => {
+                        let spacing_start = extent(&bar).0.min(group.first_line);
+                        let spacing_line =
+                            final_lines.entry(spacing_start).or_insert(FinalLine::new());
 
-                    let spacing_line = final_lines.entry(spacing_end).or_insert(FinalLine::new());
+                        // likewise above the labels of arms pointing up
 
-                    spacing_line.spacing += 2;
-                    (spacing_end, spacing_line.spacing)
+                        // the depth counts up to the first row of the label
+                        spacing_line.above += 1 + msg_lines.len();
+                        (spacing_start, spacing_line.above)
+                    }
                 };
 
                 multiline_commands.push(MultilineCommand {
-                    start_line: msg.start_line,
-                    end_line: msg.end_line,
-                    msg: msg.msg,
-                    color: msg.color,
+                    spans: bar
+                        .iter()
+                        .map(|msg| (msg.start_line, msg.end_line))
+                        .collect(),
+                    msg: msg_lines,
+                    color: bar[0].color,
                     depth,
-                    side_height: side,
-                    spacing_end,
+                    horiz,
+                    label_line,
                 })
             }
         }
 
         {
             let keys = final_lines.keys().copied().collect::<Vec<_>>();
+            let last = keys.last().copied();
             for idx in keys {
-                if final_lines[&idx].spacing == 0 && !final_lines.contains_key(&(idx + 1)) {
+                if final_lines[&idx].spacing == 0
+                    && !final_lines.contains_key(&(idx + 1))
+                    && Some(idx) != last
+                {
                     final_lines.get_mut(&idx).unwrap().spacing += 1;
                 }
             }
         }
 
-        #[derive(Debug, Clone, Copy)]
-        struct BoardCell {
-            color: Option<Color>,
-            ch: char,
-        }
-        #[derive(Debug, Clone)]
-        struct BoardRow {
-            line: Option<usize>,
-            cells: Vec<BoardCell>,
-            end_str: Option<String>,
-        }
-        impl BoardRow {
-            pub fn recolor(&mut self, span: MessageSpan, color: Option<Color>) {
-                for i in span.start..span.end {
-                    if let Some(c) = self.get_cell(i) {
-                        c.color = color;
-                    }
-                }
-            }
-            pub fn _write(&mut self, text: &str, start: usize) {
-                for (i, ch) in text.chars().enumerate() {
-                    self.write_char(ch, i + start);
-                }
-            }
-            pub fn write_colored(&mut self, text: &str, start: usize, color: Option<Color>) {
-                for (i, ch) in text.chars().enumerate() {
-                    self.write_colored_char(ch, i + start, color);
-                }
-            }
-            pub fn write_char(&mut self, ch: char, idx: usize) {
-                if let Some(c) = self.get_cell(idx) {
-                    c.ch = ch;
-                }
-            }
-            pub fn write_colored_char(&mut self, ch: char, idx: usize, color: Option<Color>) {
-                if let Some(c) = self.get_cell(idx) {
-                    c.ch = ch;
-                    c.color = color;
-                }
+        let mut board: Vec<BoardRow> = vec![];
+
+        for (line, info) in &final_lines {
+            let line_columns = &columns[line];
+            // trailing whitespace is only kept if a message highlights it
+            let highlighted_len = info
+                .multiline_highlights
+                .iter()
+                .chain(&info.underline_highlights)
+                .map(|(span, _, _)| span.end)
+                .max()
+                .unwrap_or(0);
+            let visible_len = line_columns
+                .end_col(self.trimmed_len(&lines, *line))
+                .max(highlighted_len.min(line_columns.end_col(lines.content_len(*line))));
+
+            for _ in 0..(info.above) {
+                board.push(BoardRow {
+                    line: None,
+                    cells: vec![],
+                    end_str: None,
+                    rtl: false,
+                    context: false,
+                });
             }
-            pub fn get_cell(&mut self, idx: usize) -> Option<&mut BoardCell> {
-                if self.end_str.is_some() {
-                    return self.cells.get_mut(idx);
-                }
 
-                if idx >= self.cells.len() {
-                    self.cells.resize(
-                        idx + 1,
-                        BoardCell {
-                            color: None,
-                            ch: ' ',
-                        },
-                    );
+            let mut cells = vec![
+                BoardCell {
+                    color: None,
+                    ch: ' ',
+                    cluster: None,
+                    reversed: false,
+                };
+                side_spaces[line]
+            ];
+            for cluster in line_columns
+                .shown_clusters()
+                .iter()
+                .take_while(|c| c.col_start < visible_len)
+            {
+                // escape sequences kept from the code are left out of the plain display
+                let text = match self.plain && self.ansi_source {
+                    true => strip_ansi(&cluster.text),
+                    false => cluster.text.clone(),
+                };
+                let mut chars = text.chars();
+                cells.push(BoardCell {
+                    color: None,
+                    ch: chars.next().unwrap_or(' '),
+                    cluster: chars.next().map(|_| text.clone()),
+                    reversed: false,
+                });
+                // the rest of a wide cluster is already printed with it, unlike the
+                // rest of a tab, which is printed as spaces
+                let rest = if cluster.text == "\t" { " " } else { "" };
+                for _ in 1..cluster.width {
+                    cells.push(BoardCell {
+                        color: None,
+                        ch: ' ',
+                        cluster: Some(rest.into()),
+                        reversed: false,
+                    });
                 }
-
-                self.cells.get_mut(idx)
             }
-        }
-
-        let mut board: Vec<BoardRow> = vec![];
-
-        for (line, info) in &final_lines {
-            let s = lines[*line].line.trim_end();
 
             board.push(BoardRow {
                 line: Some(*line),
-                cells: (Utf32String::from(" ").repeat(side_space) + s)
-                    .chars()
-                    .map(|v| BoardCell { color: None, ch: v })
-                    .collect::<Vec<_>>(),
+                cells,
                 end_str: None,
+                rtl: line_columns.rtl,
+                context: info.underline_highlights.is_empty()
+                    && !multiline_commands.iter().any(|c| {
+                        c.spans
+                            .iter()
+                            .any(|&(start, end)| (start..=end).contains(line))
+                    }),
             });
 
             for _ in 0..(info.spacing) {
@@ -416,6 +2134,8 @@ where
                     line: None,
                     cells: vec![],
                     end_str: None,
+                    rtl: false,
+                    context: false,
                 });
             }
         }
@@ -424,84 +2144,148 @@ where
             final_lines
                 .iter()
                 .take_while(|(v, _)| **v != l)
-                .map(|(_, l)| l.spacing + 1)
+                .map(|(_, l)| l.above + 1 + l.spacing)
                 .sum::<usize>()
+                + final_lines[&l].above
         };
 
         for (line, info) in &final_lines {
-            for &(span, color) in info
+            let side_space = side_spaces[line];
+            for &(span, color, reversed) in info
                 .multiline_highlights
                 .iter()
                 .chain(&info.underline_highlights)
             {
-                board[shifted_line(*line)].recolor(span.plus(side_space), Some(color));
+                let row = &mut board[shifted_line(*line)];
+                if self.theme.options.visualize_whitespace {
+                    row.mark_whitespace(
+                        span.plus(side_space),
+                        self.theme.chars.space_marker,
+                        self.theme.chars.tab_marker,
+                    );
+                }
+                row.recolor(span.plus(side_space), Some(color), reversed);
             }
         }
 
+        // the rows between two lines that aren't next to each other, where side bars
+        // are dotted to show that lines are left out. Bars stay solid through the
+        // rows of the messages between lines that are
+        let gap_rows = {
+            let mut prev = None;
+            let prev_lines = board
+                .iter()
+                .map(|row| {
+                    prev = row.line.or(prev);
+                    prev
+                })
+                .collect::<Vec<_>>();
+            let mut next = None;
+            let mut gap_rows = vec![false; board.len()];
+            for (i, row) in board.iter().enumerate().rev() {
+                next = row.line.or(next);
+                gap_rows[i] = row.line.is_none()
+                    && matches!((prev_lines[i], next), (Some(p), Some(n)) if n > p + 1);
+            }
+            gap_rows
+        };
+
         for MultilineCommand {
-            start_line,
-            end_line,
-            spacing_end,
+            spans,
+            label_line,
             msg,
             color,
             depth,
-            side_height,
+            horiz,
         } in multiline_commands
         {
-            let horiz = side_space
-                - side_height * (self.theme.sizing.side_pointer_length + 1)
-                - 2
-                - self.theme.sizing.side_pointer_length;
-            let start_line = shifted_line(start_line);
-            let end_line = shifted_line(end_line);
-            let spacing_end = shifted_line(spacing_end);
+            let spans = spans
+                .into_iter()
+                .map(|(start, end)| (shifted_line(start), shifted_line(end)))
+                .collect::<Vec<_>>();
+            let start_line = spans[0].0;
+            let end_line = spans.iter().map(|&(_, end)| end).max().unwrap();
+            let label_line = shifted_line(label_line);
+
+            let arm = match self.theme.sizing.side_pointer_length {
+                0 => "".into(),
+                _ => format!(
+                    "{}{}",
+                    self.theme
+                        .chars
+                        .side_pointer_line
+                        .to_string()
+                        .repeat(self.theme.sizing.side_pointer_length - 1),
+                    self.theme.chars.side_pointer
+                ),
+            };
+            // the bar runs on dotted between the spans of a message
+            let mut reached = start_line;
+            for (i, &(span_start, span_end)) in spans.iter().enumerate() {
+                #[allow(clippy::needless_range_loop)]
+                for i in (reached + 1)..span_start {
+                    board[i].write_colored_char(
+                        self.theme.chars.side_vertical_dotted,
+                        horiz,
+                        Some(color),
+                    );
+                }
+                reached = reached.max(span_end);
 
-            #[allow(clippy::needless_range_loop)]
-            for i in (start_line + 1)..end_line {
-                let spacing = board[i].line.is_none();
-                board[i].write_colored_char(
-                    if spacing {
-                        self.theme.chars.side_vertical_dotted
-                    } else {
-                        self.theme.chars.side_vertical
-                    },
-                    horiz,
-                    Some(color),
-                );
-            }
+                #[allow(clippy::needless_range_loop)]
+                for i in (span_start + 1)..span_end {
+                    board[i].write_colored_char(
+                        if gap_rows[i] {
+                            self.theme.chars.side_vertical_dotted
+                        } else {
+                            self.theme.chars.side_vertical
+                        },
+                        horiz,
+                        Some(color),
+                    );
+                }
 
-            {
-                let arm = match self.theme.sizing.side_pointer_length {
-                    0 => "".into(),
-                    _ => format!(
-                        "{}{}",
-                        self.theme
-                            .chars
-                            .side_pointer_line
-                            .to_string()
-                            .repeat(self.theme.sizing.side_pointer_length - 1),
-                        self.theme.chars.side_pointer
+                // the end the label is at gets the junction, the other one the curve,
+                // unless the bar goes on to another span there
+                let (start_char, end_char) = match self.theme.options.multiline_label {
+                    MultilineLabel::End if i == 0 => {
+                        (self.theme.chars.top_curve, self.theme.chars.side_junction)
+                    }
+                    MultilineLabel::Start if i == spans.len() - 1 => (
+                        self.theme.chars.side_junction,
+                        self.theme.chars.bottom_curve,
+                    ),
+                    _ => (
+                        self.theme.chars.side_junction,
+                        self.theme.chars.side_junction,
                     ),
                 };
-
-                board[start_line].write_colored(
-                    &format!("{}{}", self.theme.chars.top_curve, arm),
-                    horiz,
-                    Some(color),
-                );
-                board[end_line].write_colored(
-                    &format!("{}{}", self.theme.chars.side_junction, arm),
+                board[span_start].write_colored(
+                    &format!("{}{}", start_char, arm),
                     horiz,
                     Some(color),
                 );
+                board[span_end].write_colored(&format!("{}{}", end_char, arm), horiz, Some(color));
             }
 
+            let (row, verticals, curve) = match self.theme.options.multiline_label {
+                MultilineLabel::End => (
+                    label_line + depth,
+                    (end_line + 1)..(label_line + depth),
+                    self.theme.chars.bottom_curve,
+                ),
+                MultilineLabel::Start => (
+                    label_line - depth,
+                    (label_line - depth + 1)..start_line,
+                    self.theme.chars.top_curve,
+                ),
+            };
             #[allow(clippy::needless_range_loop)]
-            for i in (end_line + 1)..(spacing_end + depth) {
+            for i in verticals {
                 board[i].write_colored_char(self.theme.chars.side_vertical, horiz, Some(color))
             }
             {
-                let line = &mut board[spacing_end + depth];
+                let line = &mut board[row];
 
                 let arm = match self.theme.sizing.side_arm_length {
                     0 => "".into(),
@@ -512,111 +2296,1573 @@ where
                             .msg_line
                             .to_string()
                             .repeat(self.theme.sizing.side_arm_length - 1),
-                        self.theme.chars.msg_pointer
+                        self.theme.chars.side_arm_pointer
                     ),
                 };
 
-                line.write_colored(
-                    &format!("{}{}", self.theme.chars.bottom_curve, arm),
-                    horiz,
-                    Some(color),
-                );
-                line.cells
-                    .truncate(horiz + self.theme.sizing.side_arm_length + 1);
-                line.end_str = Some(msg)
+                line.write_colored(&format!("{}{}", curve, arm), horiz, Some(color));
+                let len = horiz + self.theme.sizing.side_arm_length + 1;
+                for (i, msg) in msg.into_iter().enumerate() {
+                    board[row + i].set_end_str(len, msg);
+                }
             }
         }
 
+        // drawn last so that no connector crosses them
+        let mut suggestions = vec![];
+        let mut stacked_junctions = vec![];
         for UnderlineCommand {
             line,
-            span,
+            spans,
+            visible,
             msg,
             color,
             depth,
-            connector_pos,
+            connectors,
+            arm_end,
+            suggestion,
+            underline,
+            underline_junction,
+            stacked,
         } in underline_commands
         {
-            let line = shifted_line(line) + 1;
-            board[line].write_colored(
-                &self.theme.chars.underline.to_string().repeat(span.size()),
-                span.start + side_space,
-                Some(color),
-            );
-            board[line].write_char(
-                self.theme.chars.underline_junction,
-                connector_pos + side_space,
-            );
-            for i in 0..(depth - 1) {
-                board[line + i + 1].write_colored_char(
-                    self.theme.chars.underline_vertical,
-                    connector_pos + side_space,
-                    Some(color),
-                )
+            let side_space = side_spaces[&line];
+            let (line, junction) = match self.theme.options.arm_direction {
+                ArmDirection::Down => (shifted_line(line) + 1, self.theme.chars.underline_junction),
+                ArmDirection::Up => (
+                    shifted_line(line) - 1,
+                    self.theme.chars.underline_junction_up,
+                ),
+            };
+            let junction = underline_junction.unwrap_or(junction);
+            if let Some((row, text)) = suggestion {
+                let row = match self.theme.options.arm_direction {
+                    ArmDirection::Down => line + row,
+                    ArmDirection::Up => line - row,
+                };
+                suggestions.push((row, spans[0].start + side_space, text));
+            }
+
+            let fill = underline.unwrap_or(match self.theme.options.underline_style {
+                UnderlineStyle::Line => self.theme.chars.underline,
+                UnderlineStyle::Caret => self.theme.chars.caret,
+            });
+            // the pattern of the theme, unless the message has its own underline,
+            // repeats from the start of every span
+            let pattern = self
+                .theme
+                .chars
+                .underline_pattern
+                .filter(|p| underline.is_none() && !p.is_empty())
+                .map(|p| p.chars().collect::<Vec<_>>());
+            let fill_at = |span: &MessageSpan, col: usize| match &pattern {
+                Some(pattern) => pattern[(col - span.start) % pattern.len()],
+                None => fill,
+            };
+            for (i, span) in spans.iter().enumerate() {
+                // only the visible parts, so a span nested in this one and drawn
+                // after it splits the underline in two
+                for part in &visible[i] {
+                    board[line].write_colored(
+                        &(part.start..part.end)
+                            .map(|col| fill_at(span, col))
+                            .collect::<String>(),
+                        part.start + side_space,
+                        Some(color),
+                    );
+                }
+                // an empty span, like a point, still gets its column marked
+                if span.size() == 0 && !visible[i].is_empty() {
+                    board[line].write_colored_char(
+                        fill_at(span, span.start),
+                        span.start + side_space,
+                        Some(color),
+                    );
+                }
+                if self.theme.options.underline_style == UnderlineStyle::Line {
+                    if let Some(connector) = connectors.get(i).filter(|_| !is_flat(*span)) {
+                        board[line].write_char(junction, connector + side_space);
+                    }
+                }
             }
-            let arm_start = connector_pos + side_space;
+            let (Some(&first), Some(&last)) = (connectors.iter().min(), connectors.iter().max())
+            else {
+                // an inline label, right after the underline
+                if let Some(msg) = msg.into_iter().next() {
+                    board[line].set_end_str(spans[0].end + side_space, msg);
+                }
+                continue;
+            };
+
+            // the first label row and the rows the connectors pass through, which for
+            // arms pointing up includes the later label rows, and the chars where the
+            // connectors meet the arm
+            let (row, verticals, curve, join) = match self.theme.options.arm_direction {
+                ArmDirection::Down => (
+                    line + depth,
+                    (line + 1)..(line + depth),
+                    self.theme.chars.bottom_curve,
+                    self.theme.chars.underline_junction_up,
+                ),
+                ArmDirection::Up => {
+                    let row = line - depth - (msg.len() - 1);
+                    (
+                        row,
+                        (row + 1)..line,
+                        self.theme.chars.top_curve,
+                        self.theme.chars.underline_junction,
+                    )
+                }
+            };
+            for i in verticals {
+                for connector in &connectors {
+                    board[i].write_colored_char(
+                        self.theme.chars.underline_vertical,
+                        connector + side_space,
+                        Some(color),
+                    )
+                }
+            }
+            let arm_start = last + side_space;
             {
-                let line = &mut board[line + depth];
+                let line = &mut board[row];
 
-                let arm = match self.theme.sizing.underline_arm_length {
+                let arm = match arm_end - last {
                     0 => "".into(),
-                    _ => format!(
+                    len => format!(
                         "{}{}",
-                        self.theme
-                            .chars
-                            .msg_line
-                            .to_string()
-                            .repeat(self.theme.sizing.underline_arm_length - 1),
-                        self.theme.chars.msg_pointer
+                        self.theme.chars.msg_line.to_string().repeat(len - 1),
+                        self.theme.chars.underline_arm_pointer
                     ),
                 };
 
+                // connectors of shared spans join the arm from the first one onwards
                 line.write_colored(
-                    &format!("{}{}", self.theme.chars.bottom_curve, arm),
-                    arm_start,
+                    &self.theme.chars.msg_line.to_string().repeat(last - first),
+                    first + side_space,
                     Some(color),
                 );
-                line.cells
-                    .truncate(arm_start + self.theme.sizing.underline_arm_length + 1);
-                line.end_str = Some(msg)
+                for connector in &connectors {
+                    line.write_colored_char(join, connector + side_space, Some(color));
+                }
+                line.write_colored_char(curve, first + side_space, Some(color));
+                if stacked {
+                    stacked_junctions.push((row, first + side_space, color));
+                }
+                line.write_colored(&arm, arm_start + 1, Some(color));
+                let len = arm_end + side_space + 1;
+                for (i, msg) in msg.into_iter().enumerate() {
+                    board[row + i].set_end_str(len, msg);
+                }
+            }
+        }
+
+        for (line, depth, col, hidden) in overflows {
+            let row = match self.theme.options.arm_direction {
+                ArmDirection::Down => shifted_line(line) + 1 + depth,
+                ArmDirection::Up => shifted_line(line) - 1 - depth,
+            };
+            let text = format!("+{} more on this line", hidden);
+            board[row].set_end_str(
+                col + side_spaces[&line],
+                (self.theme.effects.line_numbers)(&text).into(),
+            );
+        }
+
+        // where a connector goes on past a stacked label its curve becomes a
+        // junction, drawn last so that the connector doesn't cover it
+        for (row, col, color) in stacked_junctions {
+            board[row].write_colored_char(self.theme.chars.side_junction, col, Some(color));
+        }
+        for (row, col, text) in suggestions {
+            board[row].write_colored(&text, col, Some(SUGGESTION_COLOR));
+        }
+
+        let mut severities: BTreeMap<usize, Severity> = BTreeMap::new();
+        for stored in self.shown_messages() {
+            let Some(severity) = stored.severity else {
+                continue;
+            };
+            for span in self.char_spans(stored, &lines) {
+                let (end_line, _) = lines.span_end(span.start, span.end);
+                for line in [lines.line_of(span.start), end_line] {
+                    let most_severe = severities.entry(line).or_insert(severity);
+                    *most_severe = (*most_severe).max(severity);
+                }
             }
         }
 
-        let max_line_num_len = (final_lines.last_key_value().unwrap().0 + 1).ilog10() as usize + 1;
-        let empty_pad = format!("{} ", " ".repeat(max_line_num_len));
+        Board {
+            rows: board,
+            gutters,
+            line_number_width,
+            gutter_len,
+            severities,
+        }
+    }
+
+    /// Returns the rows of the report display before they are rendered to
+    /// strings, with the color of every cell, so the layout can be checked
+    /// without looking at escape sequences.
+    ///
+    /// The header and frame are not included.
+    pub fn layout(&self) -> Vec<LayoutRow> {
+        self.board()
+            .rows
+            .into_iter()
+            .map(|row| LayoutRow {
+                line: row.line,
+                cells: row
+                    .cells
+                    .into_iter()
+                    .map(|c| LayoutCell {
+                        text: c.text(),
+                        color: c.color,
+                    })
+                    .collect(),
+                label: row.end_str,
+            })
+            .collect()
+    }
+
+    /// Returns the [layout](Report::layout) as a grid of cells, one for every
+    /// display column, with the labels as trailing cells (see
+    /// [`LayoutRow::cells_with_label`]). Every row is padded with blank cells
+    /// to the width of the widest one, which suits drawing the report in a
+    /// terminal UI.
+    pub fn grid(&self) -> Vec<Vec<LayoutCell>> {
+        let mut grid = self
+            .layout()
+            .iter()
+            .map(LayoutRow::cells_with_label)
+            .collect::<Vec<_>>();
+        let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+        for row in &mut grid {
+            row.resize(
+                width,
+                LayoutCell {
+                    text: " ".into(),
+                    color: None,
+                },
+            );
+        }
+        grid
+    }
+
+    /// Returns an iterator over the rows of the pretty report display,
+    /// each fully rendered and without a trailing newline.
+    ///
+    /// The whole report is laid out when this is called, as placing any label
+    /// depends on every message of its line. Only turning the laid out rows
+    /// into strings with their colors happens one row at a time, as the
+    /// iterator is advanced.
+    ///
+    /// Messages on the same line are laid out in order of their
+    /// [priority](Message::priority), then span start, then span end, so the
+    /// output does not depend on the order they were given in. Later spans
+    /// are drawn on top of earlier overlapping ones and get their labels placed
+    /// further from the code. A span nested inside an earlier one splits its
+    /// underline in two:
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let (red, blue) = ((255, 64, 112), (64, 159, 255));
+    /// let report = Report::new_char_spanned(
+    ///     "let value = compute(1, 2);",
+    ///     [(10..20, "outer", red), (13..15, "inner", blue)],
+    /// );
+    /// let underline = &report.layout()[1];
+    /// let color = |range: std::ops::Range<usize>| {
+    ///     underline.cells[range].iter().map(|c| c.color).collect::<Vec<_>>()
+    /// };
+    ///
+    /// assert_eq!(color(10..13), [Some(red); 3]);
+    /// assert_eq!(color(13..15), [Some(blue); 2]);
+    /// assert_eq!(color(15..20), [Some(red); 5]);
+    /// ```
+    ///
+    /// The arm of a label reaches past the connectors of the labels below it,
+    /// so they never run into its text:
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let report = Report::new_char_spanned(
+    ///     "a    b",
+    ///     [(0..1, "first", (255, 64, 112)), (5..6, "second", (64, 159, 255))],
+    /// );
+    /// let rows = report.render_plain().lines().map(String::from).collect::<Vec<_>>();
+    /// let b = rows[0].find('b').unwrap();
+    ///
+    /// assert_eq!(&rows[3][b..b + 1], "|");
+    /// assert!(rows[3].ends_with("|- first"));
+    /// assert!(rows[5].ends_with("\\-- second"));
+    /// ```
+    ///
+    /// No two messages on a line get their connectors in the same column unless
+    /// they cover the same columns (like two chars of one grapheme), where the
+    /// labels are stacked along one connector. A connector that would land on
+    /// another one is moved aside:
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let report = Report::new_char_spanned(
+    ///     "let abc = 5;",
+    ///     [(4..7, "word", (255, 64, 112)), (5..6, "letter", (64, 159, 255))],
+    /// );
+    /// let rows = report.render_plain().lines().map(String::from).collect::<Vec<_>>();
+    ///
+    /// assert_eq!(rows[2].matches('|').count(), 2);
+    /// assert!(rows.iter().any(|r| r.ends_with("- word")));
+    /// assert!(rows.iter().any(|r| r.ends_with("- letter")));
+    ///
+    /// let report = Report::new_char_spanned(
+    ///     "let e\u{301} = 5;",
+    ///     [(4..5, "letter", (255, 64, 112)), (5..6, "accent", (64, 159, 255))],
+    /// );
+    /// let rows = report.render_plain().lines().map(String::from).collect::<Vec<_>>();
+    ///
+    /// assert_eq!(rows[2].matches('|').count(), 1);
+    /// assert!(rows.iter().any(|r| r.ends_with("|-- accent")));
+    /// assert!(rows.iter().any(|r| r.ends_with("\\-- letter")));
+    /// ```
+    ///
+    /// Multiline messages sharing any lines are drawn as a group, where ranges
+    /// that start later are placed closer to the code. A range nested inside
+    /// another is always drawn without crossing it. Like single-line messages,
+    /// they are grouped and ordered by their position rather than the order
+    /// they were given in:
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let code = "a = {\n  b\n}";
+    /// let value = (0..11, "value", (255, 64, 112));
+    /// let block = (4..11, "block", (64, 159, 255));
+    ///
+    /// assert_eq!(
+    ///     Report::new_char_spanned(code, [value.clone(), block.clone()]).render_plain(),
+    ///     Report::new_char_spanned(code, [block, value]).render_plain(),
+    /// );
+    /// ```
+    ///
+    /// A span ending with a line break ends on the line of the break instead
+    /// of reaching into the next line, so a message can cover a whole blank
+    /// line. Blank lines get as many cells as their underlines need:
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let code = "let a = 1;\n    \nlet b = 2;";
+    /// let report = Report::new_char_spanned(code, [(11..16, "blank line", (255, 64, 112))]);
+    /// let rows = report.render_plain().lines().map(String::from).collect::<Vec<_>>();
+    ///
+    /// assert!(rows[0].starts_with("   2.  "));
+    /// assert_eq!(rows[1].trim(), "----");
+    /// assert!(rows[3].ends_with("\\-- blank line"));
+    /// assert!(!report.render_plain().contains("3."));
+    /// ```
+    ///
+    /// Lines with right-to-left text are shown in visual order and wrapped in
+    /// a left-to-right override, so underlines line up with the characters
+    /// their spans cover. A span that isn't visually contiguous is underlined
+    /// from its leftmost to its rightmost character. The
+    /// [plain display](Report::render_plain) keeps the visual order but leaves
+    /// out the override, like every other control character.
+    ///
+    /// A line with single-line messages that is also the last line of a group
+    /// of multiline messages gets the single-line labels first, with the
+    /// multiline labels below them.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let report = Report::new_char_spanned(
+    ///     "fn f() {\n    g(x); }",
+    ///     [(7..21, "body", (255, 64, 112)), (13..17, "call", (64, 159, 255))],
+    /// );
+    /// let rows = report.render_plain().lines().map(String::from).collect::<Vec<_>>();
+    /// let row_of = |label| rows.iter().position(|r| r.contains(label)).unwrap();
+    ///
+    /// assert!(rows[row_of("call")].ends_with("\\-- call"));
+    /// assert!(row_of("call") < row_of("body"));
+    /// ```
+    pub fn rows(&self) -> impl Iterator<Item = String> {
+        let Board {
+            rows: board,
+            gutters,
+            line_number_width,
+            gutter_len,
+            severities,
+        } = self.board();
+
+        let empty_pad = " ".repeat(line_number_width);
 
         let pre_pad = " ".repeat(self.theme.sizing.pre_line_number_padding);
+        let gap = match self.theme.chars.gutter_separator {
+            Some(separator) => format!(
+                " {}{}",
+                (self.theme.effects.line_numbers)(&separator.to_string()),
+                " ".repeat(self.theme.sizing.gutter_code_gap)
+            ),
+            None => " ".repeat(self.theme.sizing.gutter_code_gap),
+        };
 
-        let mut s = String::new();
+        let connect_header = self.theme.options.header_connector;
+        let header = self.source_name.as_ref().map(|name| {
+            let arrow = match connect_header {
+                true => format!(
+                    "{}{}{}",
+                    self.theme.chars.top_curve,
+                    self.theme.chars.side_pointer_line,
+                    self.theme.chars.side_pointer
+                ),
+                false => format!(
+                    "{}{}",
+                    self.theme.chars.side_pointer_line.to_string().repeat(2),
+                    self.theme.chars.side_pointer
+                ),
+            };
+            let location = self
+                .location()
+                .map(|(line, col)| format!(":{}:{col}", line - 1 + self.first_line))
+                .unwrap_or_default();
+            format!(
+                "{}{} {}{}",
+                pre_pad,
+                (self.theme.effects.line_numbers)(&arrow),
+                name,
+                location
+            )
+        });
 
-        for row in board {
-            s += &format!(
-                "{}{}  {} {}\n",
+        // runs down from the start of the header arrow to the gutter of the code
+        let header_connector = header.as_ref().filter(|_| connect_header).map(|_| {
+            format!(
+                "{}{}",
                 pre_pad,
+                (self.theme.effects.line_numbers)(&self.theme.chars.side_vertical.to_string())
+            )
+        });
+
+        // the top and bottom borders reach as far as the widest row
+        let (frame_top, frame_bottom, frame_rule) = if self.theme.options.frame {
+            let width = board
+                .iter()
+                .map(|row| {
+                    gutter_len + row.cells.len() + 1 + row.end_str.as_deref().map_or(0, visible_len)
+                })
+                .max()
+                .unwrap_or(gutter_len);
+            let border = |corner: char| {
+                let border = format!(
+                    "{}{}",
+                    corner,
+                    self.theme
+                        .chars
+                        .frame_horizontal
+                        .to_string()
+                        .repeat(width - pre_pad.len() - 1)
+                );
+                format!("{}{}", pre_pad, (self.theme.effects.frame)(&border))
+            };
+            (
+                // the header connector runs on into the frame
+                Some(border(match header_connector {
+                    Some(_) => self.theme.chars.side_junction,
+                    None => self.theme.chars.frame_top_corner,
+                })),
+                Some(border(self.theme.chars.frame_bottom_corner)),
+                format!(
+                    "{} ",
+                    (self.theme.effects.frame)(&self.theme.chars.frame_vertical.to_string())
+                ),
+            )
+        } else {
+            (None, None, String::new())
+        };
+
+        let hidden = self.messages.len() - self.shown_messages().len();
+        let more = (hidden > 0).then(|| {
+            let ellipsis = if self.plain { PLAIN_ELLIPSIS } else { ELLIPSIS };
+            format!(
+                "{}{}",
+                pre_pad,
+                (self.theme.effects.line_numbers)(&format!("{} and {} more", ellipsis, hidden))
+            )
+        });
+
+        let counts = self.severity_counts();
+        let footer = (self.summary && !counts.is_empty()).then(|| {
+            let counts = counts
+                .into_iter()
+                .map(|(severity, count)| {
+                    let text = format!("{} {}", count, severity.name(count));
+                    match self.plain {
+                        true => text,
+                        false => truecolor(&text, severity.color()),
+                    }
+                })
+                .collect::<Vec<_>>();
+            format!("{}{}", pre_pad, counts.join(", "))
+        });
+
+        let theme = self.theme.clone();
+        let plain = self.plain;
+        let base_color = self.base_color;
+        let first_line = self.first_line;
+        let line_links = self
+            .line_links
+            .as_deref()
+            .filter(|_| !plain)
+            .map(String::from);
+
+        let rows = board.into_iter().map(move |row| {
+            // a left-to-right override keeps terminals that apply the bidi algorithm
+            // themselves from reordering the line a second time
+            let (override_start, override_end) = if row.rtl && !plain {
+                ("\u{202D}", "\u{202C}")
+            } else {
+                ("", "")
+            };
+            let icon = match row.line.and_then(|line| severities.get(&line)) {
+                _ if !theme.options.severity_icons => String::new(),
+                None => "  ".into(),
+                Some(&severity) => {
+                    let icon = match severity {
+                        Severity::Error => theme.chars.error_icon,
+                        Severity::Warning => theme.chars.warning_icon,
+                        Severity::Note => theme.chars.note_icon,
+                    }
+                    .to_string();
+                    match plain {
+                        true => format!("{} ", icon),
+                        false => format!("{} ", truecolor(&icon, severity.color())),
+                    }
+                }
+            };
+            format!(
+                "{}{}{}{}{}{}{}{} {}",
+                pre_pad,
+                frame_rule,
+                icon,
                 row.line
-                    .map(|v| (self.theme.effects.line_numbers)(&format!(
-                        "{:>max_line_num_len$}.",
-                        v + 1
-                    )))
-                    .unwrap_or((self.theme.effects.line_numbers)(&empty_pad)),
+                    .and_then(|v| gutters.get(&v).map(|number| (v, number)))
+                    .map(|(line, number)| {
+                        let pad = line_number_width.saturating_sub(visible_len(number));
+                        let number = match &line_links {
+                            Some(template) => hyperlink(
+                                number,
+                                &template.replace("{n}", &(line + first_line).to_string()),
+                            ),
+                            None => number.clone(),
+                        };
+                        (theme.effects.line_numbers)(&format!("{}{}", " ".repeat(pad), number))
+                    })
+                    .unwrap_or((theme.effects.line_numbers)(&empty_pad)),
+                gap,
+                override_start,
                 row.cells
                     .iter()
                     .map(|c| {
-                        if let Some((r, g, b)) = c.color {
-                            c.ch.to_string().truecolor(r, g, b).to_string()
+                        let text = c.text();
+                        if let Some((r, g, b)) = c.color.or(base_color).filter(|_| !plain) {
+                            match c.reversed {
+                                true => truecolor_reversed(&text, (r, g, b)),
+                                false => truecolor(&text, (r, g, b)),
+                            }
+                        } else if row.context && theme.options.dim_context {
+                            (theme.effects.line_numbers)(&text)
                         } else {
-                            (self.theme.effects.unhighlighted)(&c.ch.to_string())
+                            (theme.effects.unhighlighted)(&text)
                         }
                     })
                     .collect::<String>(),
+                override_end,
                 row.end_str.unwrap_or("".into()),
             )
-        }
-        s.truncate(s.len() - 1);
-        s
+        });
+
+        let indent = " ".repeat(self.indent);
+        let row_effect = self.theme.effects.row.clone();
+
+        header
+            .into_iter()
+            .chain(header_connector)
+            .chain(frame_top)
+            .chain(rows)
+            .chain(frame_bottom)
+            .chain(more)
+            .chain(footer)
+            .map(move |row| row_effect(&format!("{}{}", indent, row)))
+    }
+
+    /// Returns the number of rows and the width in display columns of the
+    /// widest row that the report display takes up, without printing it.
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.rows().fold((0, 0), |(rows, columns), row| {
+            (rows + 1, columns.max(visible_len(&row)))
+        })
     }
 
     /// Prettily displays the code report.
-    pub fn display(self) {
-        println!("{}", self.display_str())
+    #[cfg(feature = "std")]
+    pub fn display(&self) {
+        for row in self.rows() {
+            println!("{}", row)
+        }
+    }
+    /// Prettily displays the code report with `messages` in place of its own,
+    /// like [`Report::display_str_with`].
+    #[cfg(feature = "std")]
+    pub fn display_with<I>(&self, messages: I)
+    where
+        I: IntoIterator,
+        I::Item: Into<Message<'a>>,
+    {
+        self.replacing_messages(messages).display()
+    }
+    /// Prettily displays the code report on stderr, where diagnostics usually
+    /// go, so they don't get mixed into the output of a program that is piped.
+    #[cfg(feature = "std")]
+    pub fn eprint_display(&self) {
+        for row in self.rows() {
+            eprintln!("{}", row)
+        }
+    }
+    /// Writes the pretty report display to `w`, with a newline after every row.
+    #[cfg(feature = "std")]
+    pub fn display_to_writer(&self, w: &mut impl std::io::Write) -> std::io::Result<()> {
+        for row in self.rows() {
+            writeln!(w, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the same output as [`Report::display_str`], so reports can be used
+/// with `format!`, `write!` and friends.
+impl Display for Report<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.display_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        ArmDirection, ConnectorAnchor, GutterMode, Message, Report, Theme, ThemeChars,
+        ThemeEffects, ThemeOptions, ThemeSizing, UnderlineStyle,
+    };
+
+    const RED: (u8, u8, u8) = (255, 64, 112);
+    const BLUE: (u8, u8, u8) = (64, 159, 255);
+
+    fn plain_rows(report: &Report) -> Vec<String> {
+        report.render_plain().lines().map(String::from).collect()
+    }
+
+    fn display_rows(report: &Report) -> Vec<String> {
+        crate::strip_ansi(&report.display_str())
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn identical_messages_are_drawn_once() {
+        let report =
+            Report::new_char_spanned("let foo = hello;", [(10..15, "x", RED), (10..15, "x", RED)]);
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   1.  let foo = hello; ",
+                "                 ----- ",
+                "                   | ",
+                "                   \\-- x",
+            ]
+        );
+    }
+
+    #[test]
+    fn highlighted_trailing_whitespace_is_kept() {
+        let report = Report::new_char_spanned("foo   ", [(3..6, "spaces", RED)]);
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   1.  foo    ",
+                "          --- ",
+                "           | ",
+                "           \\-- spaces",
+            ]
+        );
+    }
+
+    #[test]
+    fn highlighted_trailing_whitespace_drops_the_crlf() {
+        // the second span also covers the `\r`, which still isn't drawn
+        for span in [3..6, 3..7] {
+            let report = Report::new_char_spanned("foo   \r\nbar", [(span, "spaces", RED)]);
+
+            assert_eq!(
+                plain_rows(&report),
+                [
+                    "   1.  foo    ",
+                    "          --- ",
+                    "           | ",
+                    "           \\-- spaces",
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn multiline_groups_on_neighbouring_lines_share_columns() {
+        let report = Report::new_char_spanned(
+            "aa\nbb\ncc\ndd\nee",
+            [(0..4, "one", RED), (6..10, "two", BLUE)],
+        );
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   1.  /-> aa ",
+                "   2.  |-> bb ",
+                "       | ",
+                "       \\-- one",
+                "   3.  /-> cc ",
+                "   4.  |-> dd ",
+                "       | ",
+                "       \\-- two",
+            ]
+        );
+    }
+
+    #[test]
+    fn multiline_groups_are_merged_transitively() {
+        // the last message overlaps both groups, so all three need their own column
+        let report = Report::new_char_spanned(
+            "aa\nbb\ncc\ndd\nee",
+            [
+                (0..4, "one", RED),
+                (6..10, "two", BLUE),
+                (3..7, "three", RED),
+            ],
+        );
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   1.  /->       aa ",
+                "   2.  |->/->    bb ",
+                "   3.  |  |->/-> cc ",
+                "   4.  |  |  |-> dd ",
+                "       |  |  | ",
+                "       |  |  \\-- two",
+                "       |  | ",
+                "       |  \\-- three",
+                "       | ",
+                "       \\-- one",
+            ]
+        );
+    }
+
+    #[test]
+    fn caret_underline_anchors_at_the_start_by_default() {
+        let report = |connector_anchor| {
+            Report::new_char_spanned("let value = 5;", [(4..9, "here", RED)]).with_theme(Theme {
+                options: ThemeOptions {
+                    underline_style: UnderlineStyle::Caret,
+                    connector_anchor,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        };
+
+        assert_eq!(plain_rows(&report(None))[2], "           | ");
+        assert_eq!(
+            plain_rows(&report(Some(ConnectorAnchor::Middle)))[2],
+            "             | "
+        );
+    }
+
+    fn sized_report(sizing: ThemeSizing) -> Report<'static> {
+        Report::new_char_spanned(
+            "fn add(a: i32) {\n    a + b\n}",
+            [
+                (0..28, "body", RED),
+                (21..22, "left", BLUE),
+                (25..26, "unknown", RED),
+            ],
+        )
+        .with_theme(Theme {
+            sizing,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn compact_sizing_snapshot() {
+        assert_eq!(
+            plain_rows(&sized_report(ThemeSizing::compact())),
+            [
+                " 1. /> fn add(a: i32) { ",
+                " 2. |      a + b ",
+                "    |      -   - ",
+                "    |      \\---|- left",
+                "    |          \\- unknown",
+                " 3. |> } ",
+                "    | ",
+                "    \\- body",
+            ]
+        );
+    }
+
+    #[test]
+    fn long_side_pointers_and_arms_squeeze_the_bars() {
+        let report = sized_report(ThemeSizing {
+            underline_spacing: 0,
+            side_arm_length: 12,
+            side_pointer_length: 12,
+            ..Default::default()
+        });
+        let rows = plain_rows(&report);
+
+        assert!(rows[0].ends_with("fn add(a: i32) { "), "{rows:#?}");
+        assert!(rows.last().unwrap().ends_with("body"), "{rows:#?}");
+    }
+
+    #[test]
+    fn spacious_sizing_snapshot() {
+        assert_eq!(
+            plain_rows(&sized_report(ThemeSizing::spacious())),
+            [
+                "    1.   /--> fn add(a: i32) { ",
+                "    2.   |        a + b ",
+                "         |        -   - ",
+                "         |        |   | ",
+                "         |        |   | ",
+                "         |        \\---|- left",
+                "         |            | ",
+                "         |            | ",
+                "         |            \\--- unknown",
+                "    3.   |--> } ",
+                "         | ",
+                "         \\--- body",
+            ]
+        );
+    }
+
+    fn long_label_report() -> Report<'static> {
+        Report::new_char_spanned(
+            "let value = 5;",
+            [(4..9, "this label is much too long for one row", RED)],
+        )
+    }
+
+    #[test]
+    fn long_label_wraps_under_its_arm() {
+        let wrapped = [
+            "   1.  let value = 5; ",
+            "           ----- ",
+            "             | ",
+            "             \\-- this label is",
+            "                 much too long",
+            "                 for one row",
+        ];
+
+        assert_eq!(plain_rows(&long_label_report().with_max_width(30)), wrapped);
+        assert_eq!(
+            plain_rows(&long_label_report().with_terminal_width(30)),
+            wrapped
+        );
+        // the max width takes precedence over the terminal width
+        assert_eq!(
+            plain_rows(
+                &long_label_report()
+                    .with_max_width(30)
+                    .with_terminal_width(200)
+            ),
+            wrapped
+        );
+        for row in plain_rows(&long_label_report().with_max_width(30)) {
+            assert!(row.trim_end().len() <= 30);
+        }
+    }
+
+    // with the `terminal_size` feature the width of an actual terminal is used
+    #[cfg(not(feature = "terminal_size"))]
+    #[test]
+    fn long_label_without_a_width_is_not_wrapped() {
+        assert_eq!(
+            plain_rows(&long_label_report()),
+            [
+                "   1.  let value = 5; ",
+                "           ----- ",
+                "             | ",
+                "             \\-- this label is much too long for one row",
+            ]
+        );
+    }
+
+    #[test]
+    fn whitespace_is_visualized_only_inside_spans() {
+        let report = |visualize_whitespace| {
+            Report::new_char_spanned("let a = b\t+ c;", [(7..12, "op", RED)]).with_theme(Theme {
+                options: ThemeOptions {
+                    visualize_whitespace,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        };
+
+        assert_eq!(display_rows(&report(true))[0], "   1.  let a =·b→  +·c; ");
+        assert_eq!(plain_rows(&report(true))[0], "   1.  let a =.b>  +.c; ");
+        assert_eq!(plain_rows(&report(false))[0], "   1.  let a = b   + c; ");
+        // the underline still covers the whole tab
+        assert_eq!(plain_rows(&report(true))[1], "              ------- ");
+    }
+
+    #[test]
+    fn line_message_leaves_out_trailing_whitespace() {
+        let mut report =
+            Report::new_char_spanned("let a = 1;   \nlet b = 2;\t ", [] as [Message; 0]);
+        report.add_line_message(0, "spaces", RED);
+        report.add_line_message(1, "tab", BLUE);
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   1.  let a = 1; ",
+                "       ---------- ",
+                "            | ",
+                "            \\-- spaces",
+                "   2.  let b = 2; ",
+                "       ---------- ",
+                "            | ",
+                "            \\-- tab",
+            ]
+        );
+    }
+
+    #[test]
+    fn line_message_past_the_end_is_clamped_to_the_last_line() {
+        let mut report = Report::new_char_spanned("let a = 1;\nlet b = 2;\t ", [] as [Message; 0]);
+        report.add_line_message(9, "whole line", RED);
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   2.  let b = 2; ",
+                "       ---------- ",
+                "            | ",
+                "            \\-- whole line",
+            ]
+        );
+    }
+
+    #[test]
+    fn with_code_keeps_messages_and_settings() {
+        let report = Report::new_char_spanned("let value = 5;", [(4..9, "name", RED)])
+            .with_source_name("main.rs")
+            .with_code("let other = 6;\nfoo");
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   --> main.rs:1:5",
+                "   1.  let other = 6; ",
+                "           ----- ",
+                "             | ",
+                "             \\-- name",
+            ]
+        );
+    }
+
+    #[test]
+    fn with_code_realigns_byte_spans_to_the_new_code() {
+        // bytes 4..6 are two chars of the old code, but one of the new
+        let report =
+            Report::new_byte_spanned("let ab = 5;", [(4..6, "name", RED)]).with_code("let é = 5;");
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   1.  let é = 5; ",
+                "           - ",
+                "           | ",
+                "           \\-- name",
+            ]
+        );
+    }
+
+    #[test]
+    fn right_to_left_text_is_shown_in_visual_order() {
+        let report = Report::new_char_spanned("let שלום = 1;", [(4..8, "name", RED)]);
+
+        assert_eq!(
+            display_rows(&report)[..2],
+            [
+                "   1.  \u{202D}let 1 = םולש;\u{202C} ",
+                "               ──┬─ "
+            ]
+        );
+        // the plain display leaves out the left-to-right override
+        assert_eq!(
+            plain_rows(&report)[..2],
+            ["   1.  let 1 = םולש; ", "               ---- "]
+        );
+    }
+
+    fn arms_up() -> Theme {
+        Theme {
+            options: ThemeOptions {
+                arm_direction: ArmDirection::Up,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn arms_up_put_labels_above_the_line() {
+        let report = Report::new_char_spanned(
+            "let value = compute(1, 2);",
+            [(4..9, "name", RED), (12..19, "call", BLUE)],
+        )
+        .with_theme(arms_up());
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "                      /-- call",
+                "                      | ",
+                "             /--------|- name",
+                "             |        | ",
+                "           -----   ------- ",
+                "   1.  let value = compute(1, 2); ",
+            ]
+        );
+    }
+
+    #[test]
+    fn arms_up_keep_multiline_labels_below() {
+        let report = Report::new_char_spanned(
+            "fn a() {\n    b(1)\n}",
+            [(0..19, "body", RED), (13..17, "call", BLUE)],
+        )
+        .with_theme(arms_up());
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   1.  /-> fn a() { ",
+                "       |         /-- call",
+                "       |         | ",
+                "       |       ---- ",
+                "   2.  |       b(1) ",
+                "   3.  |-> } ",
+                "       | ",
+                "       \\-- body",
+            ]
+        );
+    }
+
+    #[test]
+    fn blocks_without_multiline_messages_get_no_side_space() {
+        let report = Report::new_char_spanned(
+            "fn a() {\n    b\n}\n\n\n\nlet value = 5;",
+            [(0..16, "body", RED), (24..29, "name", BLUE)],
+        );
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   1.  /-> fn a() { ",
+                "       : ",
+                "   3.  |-> } ",
+                "       | ",
+                "       \\-- body",
+                "   7.  let value = 5; ",
+                "           ----- ",
+                "             | ",
+                "             \\-- name",
+            ]
+        );
+
+        // the highlight is shifted by the margin of its own block
+        let layout = report.layout();
+        let highlighted = layout[5]
+            .cells
+            .iter()
+            .filter(|c| c.color == Some(BLUE))
+            .map(|c| c.text.as_str())
+            .collect::<String>();
+        assert_eq!(highlighted, "value");
+    }
+
+    fn framed_report() -> Report<'static> {
+        Report::new_char_spanned("let value = 5;", [(4..9, "a long label here", RED)]).with_theme(
+            Theme {
+                options: ThemeOptions {
+                    frame: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn frame_reaches_as_far_as_the_widest_row() {
+        let rows = display_rows(&framed_report());
+
+        assert_eq!(
+            rows,
+            [
+                "   ╭────────────────────────────────",
+                "   │ 1.  let value = 5; ",
+                "   │         ──┬── ",
+                "   │           │ ",
+                "   │           ╰── a long label here",
+                "   ╰────────────────────────────────",
+            ]
+        );
+        let width = |row: &String| row.chars().count();
+        assert_eq!(width(&rows[0]), rows.iter().map(width).max().unwrap());
+    }
+
+    #[test]
+    fn frame_goes_below_the_header_in_plain_output() {
+        assert_eq!(
+            plain_rows(&framed_report().with_source_name("main.rs")),
+            [
+                "   --> main.rs:1:5",
+                "   +--------------------------------",
+                "   | 1.  let value = 5; ",
+                "   |         ----- ",
+                "   |           | ",
+                "   |           \\-- a long label here",
+                "   +--------------------------------",
+            ]
+        );
+    }
+
+    #[test]
+    fn indent_adds_to_the_gutter_padding() {
+        let report = Report::new_char_spanned(
+            "fn a() {\n    b\n}",
+            [(0..15, "body", RED), (13..14, "this label wraps", BLUE)],
+        )
+        .with_source_name("main.rs")
+        .with_indent(4)
+        .with_max_width(30);
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "       --> main.rs:1:1",
+                "       1.  /-> fn a() { ",
+                "       2.  |->     b ",
+                "           |       - ",
+                "           |       | ",
+                "           |       \\-- this",
+                "           |           label",
+                "           |           wraps",
+                "           | ",
+                "           \\-- body",
+            ]
+        );
+
+        let mut report = report;
+        report.theme.sizing.pre_line_number_padding = 0;
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "    --> main.rs:1:1",
+                "    1.  /-> fn a() { ",
+                "    2.  |->     b ",
+                "        |       - ",
+                "        |       | ",
+                "        |       \\-- this label",
+                "        |           wraps",
+                "        | ",
+                "        \\-- body",
+            ]
+        );
+    }
+
+    #[test]
+    fn gutter_offsets_match_the_line_starts() {
+        let code = "café\nnaïve\nend";
+        let gutters = |gutter_mode, decimal_offsets| {
+            let report = Report::new_char_spanned(
+                code,
+                [(0..1, "a", RED), (5..6, "b", RED), (11..12, "c", RED)],
+            )
+            .with_theme(Theme {
+                options: ThemeOptions {
+                    gutter_mode,
+                    decimal_offsets,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            // the gutter of every row showing a line, up to the gap before the code
+            report
+                .layout()
+                .iter()
+                .zip(plain_rows(&report))
+                .filter(|(layout, _)| layout.line.is_some())
+                .map(|(_, row)| String::from(row.trim_start().split("  ").next().unwrap()))
+                .collect::<Vec<_>>()
+        };
+
+        // `é` and `ï` take up two bytes each
+        assert_eq!(
+            gutters(GutterMode::ByteOffset, false),
+            ["0x0", "0x6", "0xd"]
+        );
+        assert_eq!(gutters(GutterMode::ByteOffset, true), ["0", "6", "13"]);
+        assert_eq!(gutters(GutterMode::CharOffset, true), ["0", "5", "11"]);
+        assert_eq!(
+            gutters(GutterMode::CharOffset, false),
+            ["0x0", "0x5", "0xb"]
+        );
+        assert_eq!(gutters(GutterMode::Both, true), ["1. 0", "2. 6", "3. 13"]);
+    }
+
+    #[test]
+    fn windowed_line_cut_on_both_sides() {
+        let report =
+            Report::new_char_spanned("aaaaaaaaaa target bbbbbbbbbb", [(11..17, "here", RED)])
+                .with_max_line_display_width(12);
+
+        assert_eq!(
+            display_rows(&report)[..2],
+            ["   1.  …a target b… ", "          ───┬── "]
+        );
+    }
+
+    #[test]
+    fn windowed_line_cut_on_the_right() {
+        let report =
+            Report::new_char_spanned("aaaaaaaaaa target bbbbbbbbbb", [(0..1, "here", RED)])
+                .with_max_line_display_width(12);
+
+        assert_eq!(
+            display_rows(&report)[..2],
+            ["   1.  aaaaaaaaaa … ", "       ┬ "]
+        );
+    }
+
+    #[test]
+    fn windowed_line_cut_on_the_left() {
+        let report =
+            Report::new_char_spanned("aaaaaaaaaa target bbbbbbbbbb", [(27..28, "here", RED)])
+                .with_max_line_display_width(12);
+
+        assert_eq!(
+            display_rows(&report)[..2],
+            ["   1.  … bbbbbbbbbb ", "                  ┬ "]
+        );
+    }
+
+    #[test]
+    fn windowed_line_in_plain_output_is_ascii() {
+        let report =
+            Report::new_char_spanned("aaaaaaaaaa target bbbbbbbbbb", [(11..17, "here", RED)])
+                .with_max_line_display_width(12);
+
+        assert!(report.render_plain().is_ascii());
+        assert_eq!(
+            plain_rows(&report)[..2],
+            ["   1.  ...target... ", "          ------ "]
+        );
+    }
+
+    #[test]
+    fn windowed_line_too_narrow_for_markers_shows_one_column() {
+        for width in [0, 1] {
+            let report =
+                Report::new_char_spanned("aaaaaaaaaa target bbbbbbbbbb", [(11..17, "here", RED)])
+                    .with_max_line_display_width(width);
+
+            assert_eq!(display_rows(&report)[0], "   1.  …g… ");
+        }
+    }
+
+    #[test]
+    fn suggestion_gets_a_row_below_the_label() {
+        let report = Report::new_char_spanned(
+            "call(x, arg);",
+            [Message::new(5..6, "rename", RED).with_suggestion("y")],
+        );
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   1.  call(x, arg); ",
+                "            - ",
+                "            | ",
+                "            \\-- rename",
+                "            y ",
+            ]
+        );
+    }
+
+    #[test]
+    fn long_suggestion_leaves_other_connectors_intact() {
+        let report = Report::new_char_spanned(
+            "call(x, arg);",
+            [
+                Message::new(5..6, "rename", RED).with_suggestion("foobarbaz"),
+                Message::new(8..11, "other", BLUE),
+            ],
+        );
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   1.  call(x, arg); ",
+                "            -  --- ",
+                "            |   | ",
+                "            \\---|- rename",
+                "                | ",
+                "                \\-- other",
+                "            foobarbaz ",
+            ]
+        );
+    }
+
+    fn chars_report(chars: ThemeChars) -> Report<'static> {
+        Report::new_char_spanned(
+            "fn a() {\n    x\n    y\n\n    z\n}",
+            [
+                (0..29, "body", RED),
+                (13..14, "x", BLUE),
+                (26..27, "z", BLUE),
+            ],
+        )
+        .with_theme(Theme {
+            chars,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn side_bars_are_dotted_only_past_left_out_lines() {
+        let bar = plain_rows(&chars_report(ThemeChars::ascii()))
+            .iter()
+            .map(|row| row.chars().nth(7).unwrap())
+            .collect::<String>();
+
+        // lines 3 and 4 are left out between lines 2 and 5
+        assert_eq!(bar, "/|:::||||||\\");
+    }
+
+    #[test]
+    fn arms_end_in_their_pointers() {
+        let rows = display_rows(&chars_report(ThemeChars {
+            underline_arm_pointer: '>',
+            side_arm_pointer: '}',
+            ..ThemeChars::ascii()
+        }));
+
+        assert_eq!(rows[4], "       :       \\-> x");
+        assert_eq!(rows[11], "       \\-} body");
+    }
+
+    #[test]
+    fn underline_pattern_repeats_from_each_span_start() {
+        let report = Report::new_char_spanned(
+            "let value = other_value;",
+            [(4..9, "a", RED), (12..23, "b", BLUE)],
+        )
+        .with_theme(Theme {
+            chars: ThemeChars {
+                underline_pattern: Some("^~"),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        assert_eq!(plain_rows(&report)[1], "           ^~-~^   ^~^~^-^~^~^ ");
+    }
+
+    #[test]
+    fn gutter_separator_runs_down_every_row() {
+        let report = chars_report(ThemeChars {
+            gutter_separator: Some('│'),
+            ..Default::default()
+        });
+
+        assert!(plain_rows(&report)
+            .iter()
+            .all(|row| row.chars().nth(6) == Some('|')));
+    }
+
+    #[test]
+    fn base_color_styles_the_code_outside_spans() {
+        #[cfg(feature = "std")]
+        colored::control::set_override(true);
+        let report = Report::new_char_spanned("let a = b;", [(8..9, "here", RED)]);
+        let style_before = |display: &str, c: char| {
+            let i = display.find(c).unwrap();
+            String::from(&display[display[..i].rfind('\x1b').unwrap()..i])
+        };
+        let plain_code = report.display_str();
+        let display = report.with_base_color(BLUE).display_str();
+
+        assert!(style_before(&plain_code, 'l') != style_before(&display, 'l'));
+        assert_eq!(style_before(&display, 'l'), style_before(&display, '='));
+        assert!(style_before(&display, 'l') != style_before(&display, 'b'));
+    }
+
+    #[test]
+    fn plain_rendering_is_ascii_without_escapes() {
+        #[cfg(feature = "std")]
+        colored::control::set_override(true);
+        let report = Report::new_char_spanned(
+            "fn a() {\n    b\n}",
+            [(0..15, "body", RED), (13..14, "b", BLUE)],
+        )
+        .with_source_name("main.rs");
+        let plain = report.render_plain();
+
+        assert!(report.display_str().contains('\x1b'));
+        assert!(!plain.contains('\x1b') && plain.is_ascii(), "{plain}");
+    }
+
+    #[test]
+    fn layout_keeps_the_color_of_every_cell() {
+        let report = Report::new_char_spanned("a + b", [(0..1, "a", RED), (4..5, "b", BLUE)]);
+        let layout = report.layout();
+        let code = layout.iter().find(|row| row.line == Some(0)).unwrap();
+
+        assert_eq!(code.cells[0].color, Some(RED));
+        assert_eq!(code.cells[2].color, None);
+        assert_eq!(code.cells[4].color, Some(BLUE));
+    }
+
+    #[test]
+    fn grid_rows_are_padded_to_the_widest() {
+        let report = Report::new_char_spanned(
+            "fn a() {\n    b\n}",
+            [(0..15, "body", RED), (13..14, "a longer label", BLUE)],
+        );
+        let grid = report.grid();
+        let text =
+            |row: &[crate::LayoutCell]| row.iter().map(|c| c.text.as_str()).collect::<String>();
+
+        assert!(grid.iter().all(|row| row.len() == grid[0].len()));
+        assert!(text(&grid[0]).trim_end().ends_with("fn a() {"));
+        assert!(text(&grid[4]).ends_with("a longer label"));
+    }
+
+    #[test]
+    fn dimensions_match_the_display() {
+        let report = Report::new_char_spanned(
+            "fn a() {\n    b\n}",
+            [(0..15, "body", RED), (13..14, "a longer label", BLUE)],
+        )
+        .with_source_name("main.rs");
+        let rows = display_rows(&report);
+
+        assert_eq!(
+            report.dimensions(),
+            (
+                rows.len(),
+                rows.iter().map(|row| row.chars().count()).max().unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn line_number_format_sets_the_gutter_width() {
+        let code = (1..=10)
+            .map(|n| alloc::format!("let v{n} = {n};"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let report =
+            Report::new_char_spanned(&code, [(100..102, "nine", RED), (112..115, "ten", BLUE)])
+                .with_theme(Theme {
+                    effects: ThemeEffects::none()
+                        .with_line_number_format(|n| alloc::format!("[{n}]")),
+                    ..Default::default()
+                });
+        let rows = display_rows(&report);
+
+        assert!(rows[0].starts_with("    [9]  let v9 = 9;"), "{rows:#?}");
+        assert!(
+            rows.iter()
+                .any(|row| row.starts_with("   [10]  let v10 = 10;")),
+            "{rows:#?}"
+        );
+    }
+
+    #[test]
+    fn row_effect_applies_to_every_row() {
+        let report = Report::new_char_spanned(
+            "fn a() {\n    b\n}",
+            [
+                Message::new(0..15, "body", RED).with_severity(crate::Severity::Error),
+                Message::new(13..14, "b", BLUE),
+            ],
+        )
+        .with_source_name("main.rs")
+        .with_theme(Theme {
+            effects: ThemeEffects::none().with_row(|row| alloc::format!("> {row}")),
+            ..Default::default()
+        });
+        let display = report.display_str();
+
+        assert!(display.lines().count() > 2);
+        assert!(
+            display.lines().all(|row| row.starts_with("> ")),
+            "{display}"
+        );
+    }
+
+    #[test]
+    fn gutter_code_gap_moves_the_side_bars_too() {
+        let mut report = Report::new_char_spanned(
+            "fn a() {\n    b\n}",
+            [(0..15, "body", RED), (13..14, "b", BLUE)],
+        );
+        report.theme.sizing.gutter_code_gap = 4;
+
+        assert_eq!(
+            plain_rows(&report),
+            [
+                "   1.    /-> fn a() { ",
+                "   2.    |->     b ",
+                "         |       - ",
+                "         |       | ",
+                "         |       \\-- b",
+                "         | ",
+                "         \\-- body",
+            ]
+        );
+    }
+
+    #[test]
+    fn header_connector_joins_the_first_line_shown() {
+        let report = Report::new_char_spanned("let a = 1;\nlet b = 2;", [(15..16, "b", RED)])
+            .with_source_name("main.rs")
+            .with_theme(Theme {
+                options: ThemeOptions {
+                    header_connector: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+
+        assert_eq!(
+            plain_rows(&report)[..3],
+            ["   /-> main.rs:2:5", "   |", "   2.  let b = 2; "]
+        );
     }
 }
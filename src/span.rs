@@ -1,4 +1,6 @@
-use std::fmt::Debug;
+use core::{fmt::Debug, ops::Range};
+
+use widestring::Utf32Str;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) struct MessageSpan {
@@ -22,6 +24,11 @@ impl MessageSpan {
         self
     }
 
+    /// Returns whether `idx` is inside the span.
+    pub(crate) fn contains(&self, idx: usize) -> bool {
+        self.start <= idx && idx < self.end
+    }
+
     pub(crate) fn overlay(self, over: Self) -> SpanOverlay {
         if over.start == over.end || over.end <= self.start || over.start >= self.end {
             return SpanOverlay::Single(self);
@@ -59,22 +66,13 @@ impl MessageSpan {
 }
 
 impl Debug for MessageSpan {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}..{}", self.start, self.end)
     }
 }
 
 /// The result of overlaying a type implementing [`MessageSpan`] onto another.
 /// This result is what remains "visible" of the bottom span.
-///
-/// ```
-/// assert_eq!((10usize..15).overlay(13..17), SpanOverlay::Single(10..13));
-/// assert_eq!((10usize..15).overlay(6..12), SpanOverlay::Single(12..15));
-/// assert_eq!(
-///     (10usize..15).overlay(12..13),
-///     SpanOverlay::Double(10..12, 13..15)
-/// );
-/// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) enum SpanOverlay {
     None,
@@ -102,11 +100,144 @@ impl Iterator for SpanOverlay {
     }
 }
 
-pub(crate) fn byte_span_to_char_span(text: &str, byte_span: MessageSpan) -> MessageSpan {
-    let start = text[..byte_span.start].chars().count();
-    let size = text[byte_span.start..byte_span.end].chars().count();
-    MessageSpan {
-        start,
-        end: start + size,
+/// Swaps the ends of an inverted span, which is taken to mean the same offsets
+/// given the other way around.
+pub(crate) fn normalized(span: &Range<usize>) -> Range<usize> {
+    span.start.min(span.end)..span.start.max(span.end)
+}
+
+/// Converts a span of byte offsets into `text` into a span of char offsets,
+/// as taken by [`Report::new_char_spanned`](crate::Report::new_char_spanned).
+///
+/// Offsets inside a multibyte char are snapped outwards to its boundaries, so
+/// the char is covered by the span, and offsets past the end are clamped.
+///
+/// ```
+/// # use lyneate::byte_span_to_char_span;
+/// // `é` takes up two bytes
+/// assert_eq!(byte_span_to_char_span("café au lait", 6..8), 5..7);
+/// // ending inside `é` still covers it
+/// assert_eq!(byte_span_to_char_span("café au lait", 0..4), 0..4);
+/// ```
+pub fn byte_span_to_char_span(text: &str, byte_span: Range<usize>) -> Range<usize> {
+    let mut start = byte_span.start.min(text.len());
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = byte_span.end.clamp(start, text.len());
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+
+    let start_chars = text[..start].chars().count();
+    let size = text[start..end].chars().count();
+    start_chars..start_chars + size
+}
+
+/// Converts a span of char offsets into `text` into a span of byte offsets,
+/// the inverse of [`byte_span_to_char_span`]. Offsets past the end are clamped.
+///
+/// ```
+/// # use lyneate::char_span_to_byte_span;
+/// // `é` takes up two bytes
+/// assert_eq!(char_span_to_byte_span("café au lait", 3..7), 3..8);
+/// assert_eq!(char_span_to_byte_span("café", 2..10), 2..5);
+/// ```
+pub fn char_span_to_byte_span(text: &str, char_span: Range<usize>) -> Range<usize> {
+    let byte_offset = |chars: usize| {
+        text.char_indices()
+            .nth(chars)
+            .map_or(text.len(), |(offset, _)| offset)
+    };
+    let start = byte_offset(char_span.start);
+    start..byte_offset(char_span.end).max(start)
+}
+
+/// Converts a span in UTF-16 code units into a char-aligned one.
+///
+/// Like with bytes, offsets between the two units of a char are snapped
+/// outwards, and offsets past the end are clamped.
+pub(crate) fn utf16_span_to_char_span(code: &Utf32Str, utf16_span: Range<usize>) -> Range<usize> {
+    let mut start = None;
+    let mut end = None;
+    let mut units = 0;
+    for (i, c) in code.as_char_slice().iter().enumerate() {
+        if end.is_none() && utf16_span.end <= units {
+            end = Some(i);
+        }
+        units += c.len_utf16();
+        if start.is_none() && utf16_span.start < units {
+            start = Some(i);
+        }
+    }
+
+    let start = start.unwrap_or(code.len());
+    start..end.unwrap_or(code.len()).max(start)
+}
+
+#[cfg(test)]
+mod tests {
+    use widestring::Utf32String;
+
+    use super::{normalized, utf16_span_to_char_span, MessageSpan, SpanOverlay};
+
+    fn span(start: usize, end: usize) -> MessageSpan {
+        MessageSpan { start, end }
+    }
+
+    #[test]
+    fn overlay_keeps_the_visible_parts() {
+        assert_eq!(
+            span(10, 15).overlay(span(13, 17)),
+            SpanOverlay::Single(span(10, 13))
+        );
+        assert_eq!(
+            span(10, 15).overlay(span(6, 12)),
+            SpanOverlay::Single(span(12, 15))
+        );
+        assert_eq!(
+            span(10, 15).overlay(span(12, 13)),
+            SpanOverlay::Double(span(10, 12), span(13, 15))
+        );
+        assert_eq!(span(10, 15).overlay(span(8, 20)), SpanOverlay::None);
+        assert_eq!(
+            span(10, 15).overlay(span(12, 12)),
+            SpanOverlay::Single(span(10, 15))
+        );
+        // an empty span at the start of the covering span stays visible
+        assert_eq!(
+            span(13, 13).overlay(span(13, 17)),
+            SpanOverlay::Single(span(13, 13))
+        );
+    }
+
+    #[test]
+    fn contains_excludes_the_end() {
+        assert!(span(10, 15).contains(10) && span(10, 15).contains(14));
+        assert!(!span(10, 15).contains(15) && !span(10, 15).contains(9));
+        assert!(!span(12, 12).contains(12));
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn inverted_spans_are_normalized() {
+        assert_eq!(normalized(&(15..10)), 10..15);
+        assert_eq!(normalized(&(10..15)), 10..15);
+        assert_eq!(normalized(&(12..12)), 12..12);
+
+        let code = Utf32String::from_str("a😀b");
+        assert_eq!(utf16_span_to_char_span(&code, normalized(&(3..1))), 1..2);
+    }
+
+    #[test]
+    fn utf16_spans_snap_to_chars() {
+        // `😀` takes up two UTF-16 units
+        let code = Utf32String::from_str("a😀b");
+        assert_eq!(utf16_span_to_char_span(&code, 1..3), 1..2);
+        assert_eq!(utf16_span_to_char_span(&code, 2..3), 1..2);
+        assert_eq!(utf16_span_to_char_span(&code, 1..2), 1..2);
+        assert_eq!(utf16_span_to_char_span(&code, 3..4), 2..3);
+        assert_eq!(utf16_span_to_char_span(&code, 3..3), 2..2);
+        assert_eq!(utf16_span_to_char_span(&code, 3..10), 2..3);
     }
 }
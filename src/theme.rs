@@ -1,12 +1,24 @@
-use colored::Colorize;
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+};
+use core::fmt::{self, Debug};
+
+use crate::ansi::dimmed;
 
 /// Theme defining the characters used different components of the report display.
 ///
 /// ```rust
+/// # use lyneate::ThemeChars;
+/// # let _ =
 /// ThemeChars {
 ///     underline: 'α',
 ///     underline_junction: 'β',
 ///     underline_vertical: 'γ',
+///     underline_junction_up: 'ρ',
+///     caret: 'π',
+///     underline_pattern: Some("ϐ"),
 ///     side_vertical: 'δ',
 ///     side_vertical_dotted: 'ε',
 ///     side_pointer: 'ζ',
@@ -14,9 +26,22 @@ use colored::Colorize;
 ///     side_junction: 'θ',
 ///     bottom_curve: 'κ',
 ///     top_curve: 'λ',
-///     msg_pointer: 'μ',
+///     underline_arm_pointer: 'μ',
+///     side_arm_pointer: 'ι',
 ///     msg_line: 'ν',
+///     space_marker: 'ξ',
+///     tab_marker: 'ο',
+///     frame_vertical: 'σ',
+///     frame_horizontal: 'τ',
+///     frame_top_corner: 'υ',
+///     frame_bottom_corner: 'φ',
+///     ellipsis: 'χ',
+///     gutter_separator: Some('ς'),
+///     error_icon: 'ψ',
+///     warning_icon: 'ω',
+///     note_icon: 'ϊ',
 /// }
+/// # ;
 /// ```
 /// <img src="https://github.com/FlowVix/lyneate/blob/master/images/chars.png?raw=true" alt="test"/>
 ///
@@ -25,8 +50,13 @@ pub struct ThemeChars {
     pub underline: char,
     pub underline_junction: char,
     pub underline_vertical: char,
+    pub underline_junction_up: char,
+    pub caret: char,
+    /// Chars repeated under single-line spans in place of `underline`.
+    pub underline_pattern: Option<&'static str>,
 
     pub side_vertical: char,
+    /// Drawn instead of `side_vertical` where a side bar passes left-out lines.
     pub side_vertical_dotted: char,
     pub side_pointer: char,
     pub side_pointer_line: char,
@@ -35,38 +65,406 @@ pub struct ThemeChars {
     pub bottom_curve: char,
     pub top_curve: char,
 
-    pub msg_pointer: char,
+    /// The end of the arms from underlines to their labels.
+    pub underline_arm_pointer: char,
+    /// The end of the arms from side bars to their labels.
+    pub side_arm_pointer: char,
     pub msg_line: char,
+
+    pub space_marker: char,
+    pub tab_marker: char,
+
+    pub frame_vertical: char,
+    pub frame_horizontal: char,
+    pub frame_top_corner: char,
+    pub frame_bottom_corner: char,
+
+    /// Meant for lines left out between the ones shown. Cut-off parts of long
+    /// lines are marked with `…` whatever the theme.
+    pub ellipsis: char,
+    /// A rule between the line numbers and the code, drawn as `|` in plain output.
+    pub gutter_separator: Option<char>,
+
+    pub error_icon: char,
+    pub warning_icon: char,
+    pub note_icon: char,
 }
 
+/// A string callback applied to a part of the report display.
+pub type Effect = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// A callback producing the gutter text of a line from its 1-based number.
+pub type LineNumberFormat = Arc<dyn Fn(usize) -> String + Send + Sync>;
+
 /// Theme defining string callbacks applied to different parts of the report display.
 ///
 /// For example, you can use this in conjuction with terminal color crates
-/// to make line numbers display with color or other effects.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// to make line numbers display with color or other effects. The callbacks
+/// can be closures capturing their environment:
+///
+/// ```
+/// # use lyneate::ThemeEffects;
+/// let marker = String::from("|");
+/// let effects = ThemeEffects::default().with_line_numbers(move |s| format!("{s}{marker}"));
+/// ```
+///
+/// The gutter text of each line, `{n}.` by default, comes from
+/// `line_number_format` before the `line_numbers` effect is applied. The gutter
+/// is as wide as the widest text it produces for the lines shown.
+///
+/// `row` is applied last, to every whole row of the display, including the
+/// header and the summary, to add a prefix to each row.
+#[derive(Clone)]
 pub struct ThemeEffects {
-    pub line_numbers: fn(&str) -> String,
-    pub unhighlighted: fn(&str) -> String,
+    pub line_numbers: Effect,
+    pub unhighlighted: Effect,
+    pub frame: Effect,
+    pub line_number_format: LineNumberFormat,
+    pub row: Effect,
 }
 
 /// Theme defining the different lengths and paddings of the report display.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ThemeSizing {
     pub pre_line_number_padding: usize,
+    /// The columns between the line numbers and the code.
+    pub gutter_code_gap: usize,
 
     pub underline_spacing: usize,
     pub underline_arm_length: usize,
 
     pub side_arm_length: usize,
+    /// The length of the pointers from the side bars of multiline messages to
+    /// the code, which can be 0 to leave them out.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let mut report = Report::new_char_spanned("fn a() {\n    b\n}", [(0..15, "body", (255, 64, 112))]);
+    /// report.theme.sizing.side_pointer_length = 0;
+    ///
+    /// assert_eq!(
+    ///     report.render_plain(),
+    ///     "   1.  / fn a() { \n   2.  |     b \n       | \n       \\-- body",
+    /// );
+    /// ```
     pub side_pointer_length: usize,
+
+    /// The columns between tab stops. Tabs in the code are shown as spaces
+    /// up to the next tab stop, so that they take up as many columns as they
+    /// would in an editor and the underlines stay aligned with the code.
+    ///
+    /// ```
+    /// # use lyneate::Report;
+    /// let report = Report::new_char_spanned("\tx = 5;", [(1..2, "here", (255, 64, 112))]);
+    /// let plain = report.render_plain();
+    /// let rows = plain.lines().collect::<Vec<_>>();
+    ///
+    /// assert!(rows[0].ends_with("    x = 5; "));
+    /// assert_eq!(rows[0].find('x'), rows[1].find('-'));
+    /// assert_eq!(rows[0].find('x'), rows[2].find('|'));
+    /// ```
+    pub tab_width: usize,
 }
 
-/// A collection of the themes to be used when displaying a report.
+/// The way single-line spans are underlined.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnderlineStyle {
+    /// Draws [`ThemeChars::underline`] under the span with a
+    /// [`ThemeChars::underline_junction`] where the message arm connects.
+    #[default]
+    Line,
+    /// Fills the span with [`ThemeChars::caret`], like rustc's `^^^^`, without
+    /// a junction. Unless another [`ConnectorAnchor`] is set, the message arm
+    /// drops from the start of the span.
+    Caret,
+}
+
+/// Where along a single-line span its message arm descends from.
+///
+/// When [`ThemeOptions::connector_anchor`] is left as `None`, this follows the
+/// [`UnderlineStyle`]: the start for [`UnderlineStyle::Caret`] and the middle
+/// otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectorAnchor {
+    Start,
+    Middle,
+    End,
+}
+
+/// Which side of the code single-line message labels are placed on.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArmDirection {
+    /// Underlines and labels go below the line.
+    #[default]
+    Down,
+    /// Underlines and labels go above the line, with the connectors rising
+    /// from [`ThemeChars::underline_junction_up`]. Multiline messages are
+    /// still labelled below their last line.
+    Up,
+}
+
+/// Which end of a multiline message its label is placed at.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MultilineLabel {
+    /// Below the last line of the message's group of overlapping multiline
+    /// messages, with the side bar running down to it.
+    #[default]
+    End,
+    /// Above the first line of the group, with the side bar running up to it.
+    /// The bar leaves the start line from a [`ThemeChars::side_junction`] and
+    /// ends at the last line in a [`ThemeChars::bottom_curve`], while the label
+    /// gets the [`ThemeChars::top_curve`].
+    ///
+    /// ```
+    /// # use lyneate::{MultilineLabel, Report, Theme, ThemeChars, ThemeOptions};
+    /// let report = Report::new_char_spanned("fn main() {\n    f();\n}", [(10..22, "body", (255, 64, 112))])
+    ///     .with_theme(Theme {
+    ///         chars: ThemeChars::ascii(),
+    ///         options: ThemeOptions {
+    ///             multiline_label: MultilineLabel::Start,
+    ///             ..Default::default()
+    ///         },
+    ///         ..Default::default()
+    ///     });
+    /// let plain = report.render_plain();
+    ///
+    /// assert!(plain.find("body").unwrap() < plain.find("fn main").unwrap());
+    /// ```
+    Start,
+}
+
+/// What the gutter shows for each line.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GutterMode {
+    /// The line number, formatted by [`ThemeEffects::line_number_format`].
+    #[default]
+    LineNumber,
+    /// The offset of the first byte of the line into the code.
+    ByteOffset,
+    /// The offset of the first char of the line into the code.
+    CharOffset,
+    /// The line number followed by the byte offset.
+    Both,
+}
+
+/// The order the labels of the single-line messages on a line are placed in,
+/// from the one closest to the code to the one furthest from it. Messages of
+/// higher [priority](crate::Message::priority) still always come after the
+/// others.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LabelOrder {
+    /// By the start of the span, then its end, so the leftmost span is labeled
+    /// closest to the code.
+    #[default]
+    Start,
+    /// By the length of the span, so the longest span is labeled closest to
+    /// the code.
+    Length,
+    /// In the order the messages are given to the report.
+    Input,
+}
+
+/// Theme defining toggles for optional parts of the report display.
+///
+/// `visualize_whitespace` replaces spaces and tabs inside highlighted spans
+/// with [`ThemeChars::space_marker`] and [`ThemeChars::tab_marker`].
+///
+/// `flat_underline_width` underlines single-line spans at least that many
+/// columns wide without a junction, with the arm attached at their end
+/// whatever the [`ConnectorAnchor`], so a whole underlined line stays flat.
+///
+/// ```
+/// # use lyneate::{Report, Theme, ThemeOptions};
+/// let line = "let total = first_value + second_values;";
+/// let report = Report::new_char_spanned(line, [(0..40, "whole line", (255, 64, 112))])
+///     .with_theme(Theme {
+///         options: ThemeOptions {
+///             flat_underline_width: Some(20),
+///             ..Default::default()
+///         },
+///         ..Default::default()
+///     });
+/// let rows = report.rows().map(|r| lyneate::strip_ansi(&r)).collect::<Vec<_>>();
+///
+/// let underline = rows[1].trim_end();
+/// assert!(underline.ends_with(&"─".repeat(40)));
+/// // the arm drops from the last column of the underline
+/// assert_eq!(rows[2].trim_end().chars().count(), underline.chars().count());
+/// ```
+///
+/// `frame` draws a border above and below the report, reaching as far as its
+/// widest row, with a vertical rule left of the line numbers.
+///
+/// `severity_icons` adds a column left of the line numbers with an icon for
+/// the most [severe](crate::Message::severity) message starting or ending on
+/// each line, like [`ThemeChars::error_icon`], in the color of its severity.
+///
+/// ```
+/// # use lyneate::{Message, Report, Severity, Theme, ThemeOptions};
+/// let report = Report::new_char_spanned(
+///     "let a = 1;\nlet b = 2;",
+///     [
+///         Message::new(4..5, "unused", (255, 191, 64)).with_severity(Severity::Warning),
+///         Message::new(15..16, "shadowed", (255, 64, 112)).with_severity(Severity::Error),
+///         Message::new(0..3, "keyword", (64, 159, 255)).with_severity(Severity::Note),
+///     ],
+/// )
+/// .with_theme(Theme {
+///     options: ThemeOptions {
+///         severity_icons: true,
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// });
+/// let plain = report.render_plain();
+///
+/// assert!(plain.contains("   ! 1.  let a = 1;"));
+/// assert!(plain.contains("   x 2.  let b = 2;"));
+/// ```
+///
+/// `inline_labels` puts the label of a line with a single labeled span right
+/// after its underline, on the same row, instead of at the end of an arm. Lines
+/// where the label would run into other spans, or wouldn't fit the
+/// [maximum width](crate::Report::with_max_width), keep the arms.
+///
+/// ```
+/// # use lyneate::{Report, Theme, ThemeOptions};
+/// let report = Report::new_char_spanned("let value: u32 = \"five\";", [(17..23, "expected u32", (255, 64, 112))])
+///     .with_theme(Theme {
+///         options: ThemeOptions {
+///             inline_labels: true,
+///             ..Default::default()
+///         },
+///         ..Default::default()
+///     });
+///
+/// assert_eq!(
+///     report.render_plain(),
+///     "   1.  let value: u32 = \"five\"; \n                        ------ expected u32",
+/// );
+/// ```
+///
+/// `dim_context` applies the `line_numbers` effect (dimming, by default)
+/// instead of the `unhighlighted` one to the code of the lines no message
+/// points at, which are only shown as context (see
+/// [`Report::with_lines_shown`](crate::Report::with_lines_shown)), so the
+/// lines with messages stand out.
+///
+/// ```
+/// # use lyneate::{Report, Theme, ThemeEffects, ThemeOptions};
+/// let report = Report::new_char_spanned("let a = 1;\nlet b = a;", [(15..16, "here", (255, 64, 112))])
+///     .with_lines_shown(0..2)
+///     .with_theme(Theme {
+///         effects: ThemeEffects::none().with_line_numbers(|s| s.to_uppercase()),
+///         options: ThemeOptions {
+///             dim_context: true,
+///             ..Default::default()
+///         },
+///         ..Default::default()
+///     });
+/// let display = lyneate::strip_ansi(&report.display_str());
+///
+/// assert!(display.contains("   1.  LET A = 1;"));
+/// assert!(display.contains("   2.  let b = "));
+/// ```
+///
+/// `gutter_mode` picks what the gutter shows, like the byte offset of every
+/// line instead of its number:
+///
+/// ```
+/// # use lyneate::{GutterMode, Report, Theme, ThemeOptions};
+/// let report = Report::new_char_spanned("café\nlet b = 2;", [(0..4, "a", (255, 64, 112)), (9..10, "b", (255, 64, 112))])
+///     .with_theme(Theme {
+///         options: ThemeOptions {
+///             gutter_mode: GutterMode::ByteOffset,
+///             ..Default::default()
+///         },
+///         ..Default::default()
+///     });
+/// let plain = report.render_plain();
+///
+/// // `é` takes up two bytes, so the second line starts at byte 6
+/// assert!(plain.contains("   0x0  café"));
+/// assert!(plain.contains("   0x6  let b = 2;"));
+/// ```
+///
+/// Offsets are written in hex, unless `decimal_offsets` is set:
+///
+/// ```
+/// # use lyneate::{GutterMode, Report, Theme, ThemeOptions};
+/// let report = Report::new_char_spanned("café\nlet b = 2;", [(0..4, "a", (255, 64, 112)), (9..10, "b", (255, 64, 112))])
+///     .with_theme(Theme {
+///         options: ThemeOptions {
+///             gutter_mode: GutterMode::CharOffset,
+///             decimal_offsets: true,
+///             ..Default::default()
+///         },
+///         ..Default::default()
+///     });
+/// let plain = report.render_plain();
+///
+/// assert!(plain.contains("   0  café"));
+/// assert!(plain.contains("   5  let b = 2;"));
+/// ```
+///
+/// `label_order` picks which of the labels on a line is placed closest to the
+/// code:
+///
+/// ```
+/// # use lyneate::{LabelOrder, Report, Theme, ThemeOptions};
+/// let labels = |label_order| {
+///     let report = Report::new_char_spanned(
+///         "let total = a + b;",
+///         [(16..17, "b", (255, 64, 112)), (12..17, "sum", (64, 159, 255)), (12..13, "a", (255, 191, 64))],
+///     )
+///     .with_theme(Theme {
+///         options: ThemeOptions {
+///             label_order,
+///             ..Default::default()
+///         },
+///         ..Default::default()
+///     });
+///     report
+///         .render_plain()
+///         .lines()
+///         .skip(2)
+///         .filter_map(|row| row.split("- ").nth(1).map(str::to_owned))
+///         .collect::<Vec<_>>()
+/// };
+///
+/// assert_eq!(labels(LabelOrder::Start), ["a", "sum", "b"]);
+/// assert_eq!(labels(LabelOrder::Length), ["sum", "a", "b"]);
+/// assert_eq!(labels(LabelOrder::Input), ["b", "sum", "a"]);
+/// ```
+///
+/// `header_connector` bends the arrow of the
+/// [source name header](crate::Report::with_source_name) down into the gutter,
+/// joining it to the first line of code.
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThemeOptions {
+    pub visualize_whitespace: bool,
+    pub underline_style: UnderlineStyle,
+    pub connector_anchor: Option<ConnectorAnchor>,
+    pub arm_direction: ArmDirection,
+    pub multiline_label: MultilineLabel,
+    pub flat_underline_width: Option<usize>,
+    pub frame: bool,
+    pub severity_icons: bool,
+    pub inline_labels: bool,
+    pub dim_context: bool,
+    pub gutter_mode: GutterMode,
+    pub decimal_offsets: bool,
+    pub label_order: LabelOrder,
+    pub header_connector: bool,
+}
+
+/// A collection of the themes to be used when displaying a report.
+#[derive(Default, Debug, Clone)]
 pub struct Theme {
     pub chars: ThemeChars,
     pub effects: ThemeEffects,
     pub sizing: ThemeSizing,
+    pub options: ThemeOptions,
 }
 
 impl Default for ThemeChars {
@@ -80,6 +478,9 @@ impl ThemeChars {
             underline: '─',
             underline_junction: '┬',
             underline_vertical: '│',
+            underline_junction_up: '┴',
+            caret: '^',
+            underline_pattern: None,
             side_vertical: '│',
             side_vertical_dotted: '╵',
             side_pointer: '▶',
@@ -87,8 +488,20 @@ impl ThemeChars {
             side_junction: '├',
             bottom_curve: '╰',
             top_curve: '╭',
-            msg_pointer: '─',
+            underline_arm_pointer: '─',
+            side_arm_pointer: '─',
             msg_line: '─',
+            space_marker: '·',
+            tab_marker: '→',
+            frame_vertical: '│',
+            frame_horizontal: '─',
+            frame_top_corner: '╭',
+            frame_bottom_corner: '╰',
+            ellipsis: '⋮',
+            gutter_separator: None,
+            error_icon: '✗',
+            warning_icon: '⚠',
+            note_icon: 'ℹ',
         }
     }
     pub fn ascii() -> Self {
@@ -96,6 +509,9 @@ impl ThemeChars {
             underline: '-',
             underline_junction: '-',
             underline_vertical: '|',
+            underline_junction_up: '-',
+            caret: '^',
+            underline_pattern: None,
             side_vertical: '|',
             side_vertical_dotted: ':',
             side_pointer: '>',
@@ -103,37 +519,305 @@ impl ThemeChars {
             side_junction: '|',
             bottom_curve: '\\',
             top_curve: '/',
-            msg_pointer: '-',
+            underline_arm_pointer: '-',
+            side_arm_pointer: '-',
             msg_line: '-',
+            space_marker: '.',
+            tab_marker: '>',
+            frame_vertical: '|',
+            frame_horizontal: '-',
+            frame_top_corner: '+',
+            frame_bottom_corner: '+',
+            ellipsis: ':',
+            gutter_separator: None,
+            error_icon: 'x',
+            warning_icon: '!',
+            note_icon: 'i',
         }
     }
+    /// The box drawing chars, with the side arms of multiline messages ending
+    /// in an arrow so they stand apart from the arms of single-line ones.
+    pub fn box_drawing_arrows() -> Self {
+        Self {
+            side_arm_pointer: '▶',
+            ..Self::box_drawing_chars()
+        }
+    }
+
+    /// Sets the char both kinds of arms end in.
+    pub fn with_msg_pointer(mut self, pointer: char) -> Self {
+        self.underline_arm_pointer = pointer;
+        self.side_arm_pointer = pointer;
+        self
+    }
 }
 
 impl Default for ThemeEffects {
     fn default() -> Self {
-        Self {
-            line_numbers: |s| s.dimmed().to_string(),
-            unhighlighted: |s| s.to_string(),
-        }
+        Self::from_fns(dimmed, |s| s.to_string(), dimmed)
     }
 }
 impl ThemeEffects {
     pub fn none() -> Self {
+        Self::from_fns(|s| s.to_string(), |s| s.to_string(), |s| s.to_string())
+    }
+    /// Creates effects from plain function pointers.
+    pub fn from_fns(
+        line_numbers: fn(&str) -> String,
+        unhighlighted: fn(&str) -> String,
+        frame: fn(&str) -> String,
+    ) -> Self {
         Self {
-            line_numbers: |s| s.to_string(),
-            unhighlighted: |s| s.to_string(),
+            line_numbers: Arc::new(line_numbers),
+            unhighlighted: Arc::new(unhighlighted),
+            frame: Arc::new(frame),
+            line_number_format: Arc::new(|n| format!("{n}.")),
+            row: Arc::new(|s| s.to_string()),
         }
     }
+
+    pub fn with_line_numbers(mut self, f: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.line_numbers = Arc::new(f);
+        self
+    }
+    pub fn with_unhighlighted(
+        mut self,
+        f: impl Fn(&str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.unhighlighted = Arc::new(f);
+        self
+    }
+    pub fn with_frame(mut self, f: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.frame = Arc::new(f);
+        self
+    }
+    pub fn with_line_number_format(
+        mut self,
+        f: impl Fn(usize) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.line_number_format = Arc::new(f);
+        self
+    }
+    pub fn with_row(mut self, f: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.row = Arc::new(f);
+        self
+    }
+}
+
+impl Debug for ThemeEffects {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ThemeEffects").finish_non_exhaustive()
+    }
 }
 
 impl Default for ThemeSizing {
     fn default() -> Self {
         Self {
             pre_line_number_padding: 3,
+            gutter_code_gap: 2,
             underline_spacing: 1,
             underline_arm_length: 2,
             side_arm_length: 2,
             side_pointer_length: 2,
+            tab_width: 4,
         }
     }
 }
+impl ThemeSizing {
+    /// The tightest sizing that still leaves room for every arm and pointer.
+    pub fn compact() -> Self {
+        Self {
+            pre_line_number_padding: 1,
+            gutter_code_gap: 1,
+            underline_spacing: 0,
+            underline_arm_length: 1,
+            side_arm_length: 1,
+            side_pointer_length: 1,
+            tab_width: 4,
+        }
+    }
+    /// A roomier sizing with longer arms and more space between messages.
+    pub fn spacious() -> Self {
+        Self {
+            pre_line_number_padding: 4,
+            gutter_code_gap: 3,
+            underline_spacing: 2,
+            underline_arm_length: 3,
+            side_arm_length: 3,
+            side_pointer_length: 3,
+            tab_width: 4,
+        }
+    }
+
+    /// Creates a sizing from its lengths in field order, checking it with
+    /// [`validate`](Self::validate).
+    ///
+    /// ```
+    /// # use lyneate::{SizingError, ThemeSizing};
+    /// assert_eq!(ThemeSizing::try_new(3, 2, 1, 2, 2, 2, 4), Ok(ThemeSizing::default()));
+    /// assert_eq!(ThemeSizing::try_new(3, 2, 1, 2, 2, 2, 0), Err(SizingError::ZeroTabWidth));
+    /// ```
+    pub fn try_new(
+        pre_line_number_padding: usize,
+        gutter_code_gap: usize,
+        underline_spacing: usize,
+        underline_arm_length: usize,
+        side_arm_length: usize,
+        side_pointer_length: usize,
+        tab_width: usize,
+    ) -> Result<Self, SizingError> {
+        let sizing = Self {
+            pre_line_number_padding,
+            gutter_code_gap,
+            underline_spacing,
+            underline_arm_length,
+            side_arm_length,
+            side_pointer_length,
+            tab_width,
+        };
+        sizing.validate().map(|()| sizing)
+    }
+
+    /// Checks that the sizing can be drawn sensibly:
+    ///
+    /// - `tab_width` is at least 1, as every tab has to reach a tab stop.
+    /// - `gutter_code_gap` is at least 1, so that the code and the side bars
+    ///   of multiline messages don't run into the line numbers.
+    /// - `underline_arm_length` and `side_arm_length` are at least 1, so that
+    ///   every arm ends in its pointer before the label.
+    ///
+    /// The other lengths don't depend on each other: a `side_pointer_length`
+    /// or `side_arm_length` too long for the space beside the code squeezes
+    /// the side bars together, whatever the `underline_spacing`, rather than
+    /// underflowing. Sizings aren't checked when they are used, and draw a tab
+    /// width of 0 as 1.
+    ///
+    /// ```
+    /// # use lyneate::{SizingError, ThemeSizing};
+    /// assert_eq!(ThemeSizing::default().validate(), Ok(()));
+    ///
+    /// let sizing = ThemeSizing { tab_width: 0, ..Default::default() };
+    /// assert_eq!(sizing.validate(), Err(SizingError::ZeroTabWidth));
+    /// ```
+    pub fn validate(&self) -> Result<(), SizingError> {
+        if self.tab_width == 0 {
+            return Err(SizingError::ZeroTabWidth);
+        }
+        if self.gutter_code_gap == 0 {
+            return Err(SizingError::NoGutterGap);
+        }
+        if self.underline_arm_length == 0 {
+            return Err(SizingError::ZeroUnderlineArm);
+        }
+        if self.side_arm_length == 0 {
+            return Err(SizingError::ZeroSideArm);
+        }
+        Ok(())
+    }
+}
+
+/// The error returned by [`ThemeSizing::validate`] for a sizing that can't be
+/// drawn sensibly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizingError {
+    /// The tab width is 0.
+    ZeroTabWidth,
+    /// There is no gap between the line numbers and the code.
+    NoGutterGap,
+    /// The arms from the underlines to their labels have no length.
+    ZeroUnderlineArm,
+    /// The arms from the side bars to their labels have no length.
+    ZeroSideArm,
+}
+
+impl fmt::Display for SizingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroTabWidth => write!(f, "tab width must be at least 1"),
+            Self::NoGutterGap => write!(f, "gap between gutter and code must be at least 1"),
+            Self::ZeroUnderlineArm => write!(f, "underline arm length must be at least 1"),
+            Self::ZeroSideArm => write!(f, "side arm length must be at least 1"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SizingError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{SizingError, ThemeSizing};
+
+    #[test]
+    fn preset_sizings_are_valid() {
+        for sizing in [
+            ThemeSizing::default(),
+            ThemeSizing::compact(),
+            ThemeSizing::spacious(),
+        ] {
+            assert_eq!(sizing.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn zero_lengths_are_rejected() {
+        let default = ThemeSizing::default();
+
+        assert_eq!(
+            ThemeSizing {
+                tab_width: 0,
+                ..default
+            }
+            .validate(),
+            Err(SizingError::ZeroTabWidth)
+        );
+        assert_eq!(
+            ThemeSizing {
+                gutter_code_gap: 0,
+                ..default
+            }
+            .validate(),
+            Err(SizingError::NoGutterGap)
+        );
+        assert_eq!(
+            ThemeSizing {
+                underline_arm_length: 0,
+                ..default
+            }
+            .validate(),
+            Err(SizingError::ZeroUnderlineArm)
+        );
+        assert_eq!(
+            ThemeSizing {
+                side_arm_length: 0,
+                ..default
+            }
+            .validate(),
+            Err(SizingError::ZeroSideArm)
+        );
+    }
+
+    #[test]
+    fn try_new_checks_the_sizing() {
+        assert_eq!(
+            ThemeSizing::try_new(1, 1, 0, 1, 1, 1, 4),
+            Ok(ThemeSizing::compact())
+        );
+        assert_eq!(
+            ThemeSizing::try_new(1, 0, 0, 1, 1, 1, 4),
+            Err(SizingError::NoGutterGap)
+        );
+    }
+
+    #[test]
+    fn zero_paddings_and_pointers_are_allowed() {
+        let sizing = ThemeSizing {
+            pre_line_number_padding: 0,
+            underline_spacing: 0,
+            side_pointer_length: 0,
+            ..Default::default()
+        };
+
+        assert_eq!(sizing.validate(), Ok(()));
+    }
+}
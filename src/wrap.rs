@@ -0,0 +1,83 @@
+use alloc::{string::String, vec, vec::Vec};
+
+use unicode_width::UnicodeWidthChar;
+
+/// Returns the display width of `text`, skipping over ANSI escape
+/// sequences (such as the ones added by `colored`).
+pub(crate) fn visible_len(text: &str) -> usize {
+    let mut len = 0;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            skip_escape(&mut chars);
+        } else {
+            len += c.width().unwrap_or(0);
+        }
+    }
+    len
+}
+
+/// Advances `chars` past the rest of an escape sequence whose leading `ESC`
+/// has already been consumed.
+pub(crate) fn skip_escape(chars: &mut core::str::Chars) {
+    match chars.clone().next() {
+        Some('[') => {
+            chars.next();
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        }
+        // operating system commands, like OSC 8 links, end in BEL or `ESC \`
+        Some(']') => {
+            chars.next();
+            while let Some(c) = chars.next() {
+                if c == '\x07' || (c == '\x1b' && chars.next().is_some()) {
+                    break;
+                }
+            }
+        }
+        _ => {
+            chars.next();
+        }
+    }
+}
+
+/// Splits `text` into lines that are at most `width` columns wide, breaking
+/// at spaces. Words longer than `width` are put on a line of their own rather
+/// than being split.
+pub(crate) fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut out = vec![];
+    let mut current = String::new();
+    let mut current_len = 0;
+
+    for word in text.split(' ') {
+        let word_len = visible_len(word);
+
+        if current_len > 0 && current_len + 1 + word_len > width {
+            out.push(core::mem::take(&mut current));
+            current_len = 0;
+        }
+        if current_len > 0 || !current.is_empty() {
+            current.push(' ');
+            current_len += 1;
+        }
+        current += word;
+        current_len += word_len;
+    }
+    out.push(current);
+    out
+}
+
+/// Queries the width of the terminal attached to stdout, if there is one.
+#[cfg(feature = "terminal_size")]
+pub(crate) fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(terminal_size::Width(w), _)| w as usize)
+}
+
+#[cfg(not(feature = "terminal_size"))]
+pub(crate) fn terminal_width() -> Option<usize> {
+    None
+}